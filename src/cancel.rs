@@ -0,0 +1,90 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::{future::BoxFuture, Future};
+use pg::{
+    tls::{MakeTlsConnect, TlsConnect},
+    CancelToken, Error as PgError, Socket,
+};
+
+use crate::{Error, Health};
+
+/// Sends a cancellation request for the query running on a connection.
+///
+/// This is provided by the [`Connector`](crate::Connector), as it owns the TLS configuration
+/// needed to open the side-channel connection the cancel request is sent over.
+pub type Canceller = Arc<dyn Fn(CancelToken) -> BoxFuture<'static, Result<(), PgError>> + Send + Sync>;
+
+/// Create a [`Canceller`] sending cancel requests over the given TLS connector
+pub fn canceller<T>(tls: T) -> Canceller
+where
+    T: MakeTlsConnect<Socket> + Clone + Sync + Send + 'static,
+    T::Stream: Sync + Send,
+    T::TlsConnect: Sync + Send + TlsConnect<Socket, Future: Send>,
+{
+    Arc::new(move |token: CancelToken| {
+        let tls = tls.clone();
+        Box::pin(async move { token.cancel_query(tls).await })
+    })
+}
+
+/// Fires the cancel token when dropped, unless disarmed first
+pub(crate) struct CancelGuard {
+    token: Option<CancelToken>,
+    canceller: Canceller,
+}
+
+impl CancelGuard {
+    pub fn new(token: CancelToken, canceller: Canceller) -> Self {
+        CancelGuard {
+            token: Some(token),
+            canceller,
+        }
+    }
+
+    pub fn disarm(mut self) {
+        self.token = None;
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        let Some(token) = self.token.take() else { return };
+
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            log::warn!("Unable to cancel query outside of a runtime");
+            return;
+        };
+
+        let cancelling = (self.canceller)(token);
+
+        _ = handle.spawn(async move {
+            if let Err(e) = cancelling.await {
+                log::warn!("Error cancelling query: {e}");
+            }
+        });
+    }
+}
+
+/// Run `future` with an optional timeout, cancelling the query on the server
+/// if the timeout elapses or the future is dropped before completion.
+///
+/// The connection is poisoned if cancelled, as it may be left mid-statement.
+pub(crate) async fn cancellable<O>(
+    token: CancelToken,
+    canceller: Canceller,
+    health: &Health,
+    duration: Option<Duration>,
+    future: impl Future<Output = Result<O, Error>>,
+) -> Result<O, Error> {
+    let guard = CancelGuard::new(token, canceller);
+    let in_flight = health.begin();
+
+    let res = crate::timeout(duration, future).await;
+
+    if !matches!(res, Err(Error::TimeoutError(_))) {
+        guard.disarm();
+        in_flight.finish();
+    }
+
+    res
+}
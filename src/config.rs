@@ -1,6 +1,8 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use pg::Config as PgConfig;
+use pg::{error::DbError, Config as PgConfig};
+
+use crate::util::quote_literal;
 
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Timeouts {
@@ -10,6 +12,8 @@ pub struct Timeouts {
     pub create: Option<Duration>,
     /// Timeout when recycling an object
     pub recycle: Option<Duration>,
+    /// Timeout for queries run through the `*_with_timeout` methods
+    pub query: Option<Duration>,
 }
 
 impl Timeouts {
@@ -19,6 +23,7 @@ impl Timeouts {
             wait: None,
             create: None,
             recycle: None,
+            query: None,
         }
     }
 
@@ -36,6 +41,102 @@ impl Timeouts {
         self.recycle = Some(timeout);
         self
     }
+
+    pub const fn query(mut self, timeout: Duration) -> Self {
+        self.query = Some(timeout);
+        self
+    }
+}
+
+/// Backoff policy used when (re)trying to establish a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Delay before the first retry
+    pub initial_delay: Duration,
+    /// Factor the delay grows by after each failed attempt, in percent (`200` doubles it)
+    pub multiplier_percent: u32,
+    /// Upper bound on the delay between attempts
+    pub max_delay: Duration,
+    /// Use "full jitter", sleeping a random duration between zero and the computed delay
+    pub jitter: bool,
+    /// Give up once this much time has passed since the first attempt
+    pub max_elapsed: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    /// Create a retry policy starting at 100ms, doubling up to 10s, with full jitter
+    pub const fn new() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            multiplier_percent: 200,
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            max_elapsed: None,
+        }
+    }
+
+    pub const fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Set the growth factor, rounded to the nearest percent
+    pub const fn multiplier(mut self, multiplier: f64) -> Self {
+        // saturating cast, with NaN becoming zero
+        self.multiplier_percent = (multiplier * 100.0 + 0.5) as u32;
+        self
+    }
+
+    pub const fn multiplier_percent(mut self, percent: u32) -> Self {
+        self.multiplier_percent = percent;
+        self
+    }
+
+    pub const fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub const fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub const fn max_elapsed(mut self, elapsed: Duration) -> Self {
+        self.max_elapsed = Some(elapsed);
+        self
+    }
+
+    /// Compute how long to sleep after the given number of failed attempts (starting at 1)
+    pub fn delay(&self, failures: u32) -> Duration {
+        let exp = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+
+        let multiplier = self.multiplier_percent as f64 / 100.0;
+
+        let mut secs = (self.initial_delay.as_secs_f64() * multiplier.powi(exp))
+            .min(self.max_delay.as_secs_f64())
+            .max(0.0);
+
+        if self.jitter {
+            secs *= crate::util::random_unit();
+        }
+
+        Duration::from_secs_f64(secs)
+    }
+
+    /// Returns true if sleeping for `delay` after `elapsed` would exceed `max_elapsed`
+    pub fn is_exhausted(&self, elapsed: Duration, delay: Duration) -> bool {
+        match self.max_elapsed {
+            Some(max) => elapsed.saturating_add(delay) > max,
+            None => false,
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,6 +148,12 @@ pub enum RecyclingMethod {
 }
 
 impl RecyclingMethod {
+    /// Returns true if recycling with this method wipes session state,
+    /// requiring session parameters to be applied again.
+    pub const fn resets_session(self) -> bool {
+        matches!(self, RecyclingMethod::Clean)
+    }
+
     pub fn query(self) -> Option<&'static str> {
         match self {
             RecyclingMethod::Fast => None,
@@ -64,6 +171,81 @@ impl RecyclingMethod {
     }
 }
 
+/// What to do when notifications arrive faster than they are received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationOverflow {
+    /// Wait for space in the channel, stalling the connection.
+    /// If a timeout is given, the notification is dropped once it elapses.
+    Block(Option<Duration>),
+    /// Drop the incoming notification
+    DropNewest,
+    /// Spill notifications that don't fit into the channel into a second buffer of up to `channel_size`,
+    /// dropping the oldest spilled notification when that is full too.
+    ///
+    /// Notifications already in the channel are never dropped, so up to twice `channel_size` may be held.
+    /// Connections driven directly (see [`NotificationMode::OnDemand`]) have no second buffer,
+    /// and drop the incoming notification as with `DropNewest`.
+    Spill,
+    /// Close the connection
+    Disconnect,
+}
+
+impl Default for NotificationOverflow {
+    fn default() -> Self {
+        NotificationOverflow::Block(Some(Duration::from_secs(3)))
+    }
+}
+
+/// How notifications are handled on new connections
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationMode {
+    /// Drive each connection from a task that forwards notifications to the client
+    #[default]
+    Forward,
+    /// Drive each connection directly, only forwarding notifications once requested,
+    /// such as by [`Client::listen`](crate::Client::listen).
+    OnDemand,
+}
+
+/// How `LISTEN` subscriptions made through [`Client::listen`](crate::Client::listen) are handled when recycling
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenRecycling {
+    /// Unsubscribe from all channels and discard any pending notifications
+    #[default]
+    Reset,
+    /// Keep subscriptions, re-establishing them if the recycling method clears them.
+    ///
+    /// Connections with active subscriptions are set aside and preferentially handed out
+    /// again by [`Pool::get_listening`](crate::Pool::get_listening) for the same channels.
+    /// At most [`PoolConfig::max_idle_listeners`] are kept; beyond that, or when the pool
+    /// has no other idle connections, the longest-parked one is reset and reused.
+    Preserve,
+}
+
+/// Callback invoked with every notice sent by the database, such as from `RAISE WARNING`
+#[derive(Clone)]
+pub struct NoticeHandler(pub Arc<dyn Fn(&DbError) + Send + Sync>);
+
+impl NoticeHandler {
+    pub fn new(f: impl Fn(&DbError) + Send + Sync + 'static) -> Self {
+        NoticeHandler(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for NoticeHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NoticeHandler(..)")
+    }
+}
+
+impl PartialEq for NoticeHandler {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for NoticeHandler {}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct PoolConfig {
     pub pg_config: PgConfig,
@@ -71,8 +253,33 @@ pub struct PoolConfig {
     pub readonly: bool,
     pub max_connections: usize,
     pub max_retries: usize,
+    pub retry_policy: RetryPolicy,
     pub channel_size: usize,
+    pub notification_mode: NotificationMode,
+    pub notification_overflow: NotificationOverflow,
+    /// Handler for database notices, which are otherwise logged
+    pub notice_handler: Option<NoticeHandler>,
     pub recycling_method: RecyclingMethod,
+    pub listen_recycling: ListenRecycling,
+    /// Maximum number of idle connections kept subscribed under [`ListenRecycling::Preserve`]
+    pub max_idle_listeners: usize,
+    /// Compatibility mode for running behind a transaction-pooling connection pooler such as PgBouncer or pgcat.
+    ///
+    /// Thorn queries are executed as unnamed statements rather than being prepared and cached,
+    /// session parameters and session-level recycling are skipped, and `LISTEN` is unavailable.
+    ///
+    /// Only unnamed statements are safe in this mode: `query_cached`/`execute_cached` given the types
+    /// of all parameters, the thorn `*2` query methods, and `query_typed` and friends on the underlying
+    /// client. The plain `query`/`execute` family and `prepare_cached` return
+    /// [`Error::Unsupported`](crate::Error::Unsupported).
+    pub transaction_pooling: bool,
+    /// Maximum number of prepared statements cached per connection, or unbounded if `None`.
+    /// A size of zero disables statement caching.
+    pub statement_cache_size: Option<usize>,
+    /// Session parameters applied to each connection, such as `search_path` or `statement_timeout`
+    pub session_params: Vec<(String, String)>,
+    /// Arbitrary SQL run on each connection after `session_params` are applied
+    pub init_sql: Option<String>,
 }
 
 impl PoolConfig {
@@ -86,8 +293,18 @@ impl PoolConfig {
                 Err(_) => 8,
             },
             max_retries: 6,
+            retry_policy: RetryPolicy::new(),
             channel_size: 64,
+            notification_mode: NotificationMode::Forward,
+            notification_overflow: NotificationOverflow::default(),
+            notice_handler: None,
             recycling_method: RecyclingMethod::Fast,
+            listen_recycling: ListenRecycling::Reset,
+            max_idle_listeners: 4,
+            transaction_pooling: false,
+            statement_cache_size: None,
+            session_params: Vec::new(),
+            init_sql: None,
         }
     }
 
@@ -106,10 +323,101 @@ impl PoolConfig {
         self
     }
 
+    pub fn notification_mode(mut self, mode: NotificationMode) -> Self {
+        self.notification_mode = mode;
+        self
+    }
+
+    pub fn notification_overflow(mut self, overflow: NotificationOverflow) -> Self {
+        self.notification_overflow = overflow;
+        self
+    }
+
+    pub fn notice_handler(mut self, f: impl Fn(&DbError) + Send + Sync + 'static) -> Self {
+        self.notice_handler = Some(NoticeHandler::new(f));
+        self
+    }
+
     pub fn max_retries(mut self, retries: usize) -> Self {
         self.max_retries = retries;
         self
     }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn recycling_method(mut self, method: RecyclingMethod) -> Self {
+        self.recycling_method = method;
+        self
+    }
+
+    pub fn listen_recycling(mut self, mode: ListenRecycling) -> Self {
+        self.listen_recycling = mode;
+        self
+    }
+
+    pub fn max_idle_listeners(mut self, max: usize) -> Self {
+        self.max_idle_listeners = max;
+        self
+    }
+
+    pub fn transaction_pooling(mut self) -> Self {
+        self.transaction_pooling = true;
+        self
+    }
+
+    /// The recycling method to actually use, as session state cannot be relied upon with transaction pooling
+    pub fn effective_recycling_method(&self) -> RecyclingMethod {
+        match self.recycling_method {
+            RecyclingMethod::Clean if self.transaction_pooling => RecyclingMethod::Verified,
+            method => method,
+        }
+    }
+
+    pub fn statement_cache_size(mut self, size: usize) -> Self {
+        self.statement_cache_size = Some(size);
+        self
+    }
+
+    /// Set a session parameter on every connection, e.g. `session_param("timezone", "UTC")`
+    pub fn session_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.session_params.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn init_sql(mut self, sql: impl Into<String>) -> Self {
+        self.init_sql = Some(sql.into());
+        self
+    }
+
+    /// Builds the SQL used to initialize a session, or `None` if there is nothing to do
+    pub fn session_sql(&self) -> Option<String> {
+        use std::fmt::Write;
+
+        if self.session_params.is_empty() && self.init_sql.is_none() {
+            return None;
+        }
+
+        let mut sql = String::new();
+
+        // set_config is used over SET so list values like `search_path` are parsed properly
+        for (name, value) in &self.session_params {
+            _ = writeln!(
+                sql,
+                "SELECT set_config({}, {}, false);",
+                quote_literal(name),
+                quote_literal(value)
+            );
+        }
+
+        if let Some(ref init_sql) = self.init_sql {
+            sql += init_sql;
+        }
+
+        Some(sql)
+    }
 }
 
 impl std::str::FromStr for PoolConfig {
@@ -0,0 +1,210 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use pg::{Client as PgClient, Notification};
+use tokio::sync::mpsc::Receiver;
+
+use crate::{Connection, Connector, Error, PoolConfig};
+
+/// User and password to connect with, and optionally when they stop being valid
+#[derive(Clone, Default)]
+pub struct Credentials {
+    pub user: Option<String>,
+    pub password: Option<Vec<u8>>,
+    pub expires_at: Option<Instant>,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("user", &self.user)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl Credentials {
+    pub fn new(user: impl Into<String>, password: impl Into<Vec<u8>>) -> Self {
+        Credentials {
+            user: Some(user.into()),
+            password: Some(password.into()),
+            expires_at: None,
+        }
+    }
+
+    pub fn expires_at(mut self, expires_at: Instant) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Returns true if the credentials are still valid `margin` from now
+    pub fn is_fresh(&self, margin: Duration) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Instant::now() + margin < expires_at,
+            None => true,
+        }
+    }
+
+    /// Overwrite the user and password of `config` with any that are set here
+    pub fn apply(&self, config: &mut pg::Config) {
+        if let Some(ref user) = self.user {
+            config.user(user);
+        }
+
+        if let Some(ref password) = self.password {
+            config.password(password);
+        }
+    }
+}
+
+/// Supplies credentials right before each new connection is made
+#[async_trait::async_trait]
+pub trait CredentialProvider {
+    async fn credentials(&self) -> Result<Credentials, Error>;
+
+    /// Called when the server rejects the last credentials, before asking for new ones
+    async fn invalidate(&self) {}
+}
+
+/// Caches credentials from another provider, refreshing them `refresh_before` their expiry.
+///
+/// If refreshing fails while the cached credentials have not yet expired, they will continue to be used.
+pub struct CachedCredentials<P> {
+    provider: P,
+    refresh_before: Duration,
+    cached: tokio::sync::Mutex<Option<Credentials>>,
+}
+
+impl<P> CachedCredentials<P> {
+    pub fn new(provider: P, refresh_before: Duration) -> Self {
+        CachedCredentials {
+            provider,
+            refresh_before,
+            cached: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Forget the cached credentials, forcing a refresh on the next connection
+    pub async fn invalidate(&self) {
+        *self.cached.lock().await = None;
+    }
+}
+
+#[async_trait::async_trait]
+impl<P> CredentialProvider for CachedCredentials<P>
+where
+    P: CredentialProvider + Send + Sync,
+{
+    async fn credentials(&self) -> Result<Credentials, Error> {
+        let mut cached = self.cached.lock().await;
+
+        if let Some(ref creds) = *cached {
+            if creds.is_fresh(self.refresh_before) {
+                return Ok(creds.clone());
+            }
+        }
+
+        match self.provider.credentials().await {
+            Ok(creds) => Ok(cached.insert(creds).clone()),
+            Err(e) => match *cached {
+                Some(ref creds) if creds.is_fresh(Duration::ZERO) => {
+                    log::warn!("Error refreshing database credentials, using cached credentials: {e}");
+                    Ok(creds.clone())
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    async fn invalidate(&self) {
+        *self.cached.lock().await = None;
+        self.provider.invalidate().await;
+    }
+}
+
+/// Reads the password from a file on every connection, allowing it to be rotated on disk
+pub struct PasswordFile {
+    pub user: Option<String>,
+    pub path: PathBuf,
+}
+
+impl PasswordFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        PasswordFile {
+            user: None,
+            path: path.into(),
+        }
+    }
+
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for PasswordFile {
+    async fn credentials(&self) -> Result<Credentials, Error> {
+        let path = self.path.clone();
+
+        let password = match tokio::task::spawn_blocking(move || std::fs::read_to_string(path)).await {
+            Ok(Ok(password)) => password,
+            Ok(Err(e)) => return Err(Error::CredentialError(Box::new(e))),
+            Err(e) => return Err(Error::CredentialError(Box::new(e))),
+        };
+
+        Ok(Credentials {
+            user: self.user.clone(),
+            password: Some(password.trim_end().as_bytes().to_vec()),
+            expires_at: None,
+        })
+    }
+}
+
+/// [`Connector`] that consults a [`CredentialProvider`] before each connection
+///
+/// If the server rejects the credentials, the provider is invalidated and connecting is retried once with fresh ones.
+pub struct WithCredentials<C, P> {
+    pub connector: C,
+    pub provider: P,
+}
+
+impl<C, P> WithCredentials<C, P> {
+    pub fn new(connector: C, provider: P) -> Self {
+        WithCredentials { connector, provider }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C, P> Connector for WithCredentials<C, P>
+where
+    C: Connector + Send + Sync,
+    P: CredentialProvider + Send + Sync,
+{
+    async fn connect(
+        &self,
+        config: &PoolConfig,
+    ) -> Result<(PgClient, Connection, Receiver<Notification>), Error> {
+        let credentials = self.provider.credentials().await?;
+
+        let mut config = config.clone();
+        credentials.apply(&mut config.pg_config);
+
+        match self.connector.connect(&config).await {
+            Err(e) if e.is_auth_failure() => {
+                log::warn!("Database credentials rejected, refreshing: {e}");
+
+                self.provider.invalidate().await;
+
+                let credentials = self.provider.credentials().await?;
+                credentials.apply(&mut config.pg_config);
+
+                self.connector.connect(&config).await
+            }
+            res => res,
+        }
+    }
+}
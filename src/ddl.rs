@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+
+use crate::{util::quote_literal, Error, ListenEvent, Pool};
+
+/// Channel notified by the trigger installed with [`Pool::install_ddl_trigger`] by default
+pub const DEFAULT_DDL_CHANNEL: &str = "pg_pool_ddl";
+
+impl Pool {
+    /// Install an event trigger that notifies `channel` with the command tag at the end of every DDL command.
+    ///
+    /// This requires superuser privileges, and only needs to be done once per database.
+    pub async fn install_ddl_trigger(&self, channel: &str) -> Result<(), Error> {
+        let sql = format!(
+            "CREATE OR REPLACE FUNCTION pg_pool_notify_ddl() RETURNS event_trigger LANGUAGE plpgsql AS $$
+            BEGIN
+                PERFORM pg_notify({}, tg_tag);
+            END
+            $$;
+            DROP EVENT TRIGGER IF EXISTS pg_pool_notify_ddl;
+            CREATE EVENT TRIGGER pg_pool_notify_ddl ON ddl_command_end EXECUTE FUNCTION pg_pool_notify_ddl();",
+            quote_literal(channel)
+        );
+
+        self.get().await?.client.batch_execute(&sql).await?;
+
+        Ok(())
+    }
+
+    /// Listen on `channel` for schema changes, clearing all statement caches whenever one occurs.
+    ///
+    /// Caches are also cleared if the listener had to reconnect, as changes may have been missed.
+    /// The task ends once the pool is dropped, or it can be aborted through the returned handle.
+    pub fn invalidate_on_ddl(&self, channel: &str) -> Result<JoinHandle<()>, Error> {
+        let mut listener = self.listen([channel])?;
+        let pool = Arc::downgrade(&self.0);
+
+        Ok(tokio::spawn(async move {
+            while let Some(event) = listener.next().await {
+                let Some(pool) = pool.upgrade() else { break };
+
+                match event {
+                    ListenEvent::Notification(notif) => {
+                        log::info!("Schema change ({}), clearing statement caches", notif.payload());
+                    }
+                    ListenEvent::Gap => {
+                        log::info!("Schema change listener reconnected, clearing statement caches");
+                    }
+                }
+
+                pool.stmt_caches.clear();
+            }
+        }))
+    }
+}
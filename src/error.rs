@@ -18,6 +18,27 @@ pub enum Error {
     #[error("Could not connect to database")]
     ConnectionFailure,
 
+    #[error(
+        "Statement cache collision on row type `{row_type}`, cached \"{cached}\" but requested \"{query}\""
+    )]
+    StatementCacheCollision {
+        row_type: &'static str,
+        cached: String,
+        query: String,
+    },
+
+    #[error("Unsupported: {0}")]
+    Unsupported(&'static str),
+
+    #[error("Credential Error: {0}")]
+    CredentialError(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Error decoding payload on channel \"{channel}\": {error}")]
+    PayloadError {
+        channel: String,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+
     #[error("Thorn Format Error: {0}")]
     FormatError(#[from] thorn::macros::SqlFormatError),
 }
@@ -29,4 +50,50 @@ impl Error {
             _ => None,
         }
     }
+
+    /// Returns true if the server rejected the credentials used to connect
+    pub fn is_auth_failure(&self) -> bool {
+        use pg::error::SqlState;
+
+        match self.as_db_error() {
+            Some(e) => matches!(
+                *e.code(),
+                SqlState::INVALID_PASSWORD | SqlState::INVALID_AUTHORIZATION_SPECIFICATION
+            ),
+            None => false,
+        }
+    }
+
+    /// Returns true if this error leaves the connection unusable, such as the connection being closed,
+    /// an I/O or protocol error, or the server terminating the session.
+    pub fn is_fatal(&self) -> bool {
+        use pg::error::Severity;
+
+        let Error::DbError(e) = self else {
+            return false;
+        };
+
+        if e.is_closed() {
+            return true;
+        }
+
+        match e.as_db_error() {
+            Some(db) => matches!(db.parsed_severity(), Some(Severity::Fatal | Severity::Panic)),
+            None => std::error::Error::source(e).is_some_and(|e| e.is::<std::io::Error>()),
+        }
+    }
+
+    /// Returns true if this error was caused by a cached prepared statement
+    /// becoming invalid, such as after a migration or behind a connection pooler.
+    pub fn is_stale_statement(&self) -> bool {
+        use pg::error::SqlState;
+
+        match self.as_db_error() {
+            Some(e) if *e.code() == SqlState::INVALID_SQL_STATEMENT_NAME => true,
+            Some(e) if *e.code() == SqlState::FEATURE_NOT_SUPPORTED => {
+                e.message().contains("cached plan must not change result type")
+            }
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,98 @@
+use pg::Notification;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc::Receiver,
+};
+
+/// Broadcasts notifications to any number of in-process subscribers,
+/// each receiving their own copy of notifications on the channels they're interested in.
+#[derive(Clone)]
+pub struct NotificationHub {
+    tx: broadcast::Sender<Notification>,
+}
+
+impl NotificationHub {
+    pub fn new(capacity: usize) -> Self {
+        NotificationHub {
+            tx: broadcast::channel(capacity.max(1)).0,
+        }
+    }
+
+    /// Create a hub fed by forwarding everything from `rx` in a background task
+    pub fn forward(mut rx: Receiver<Notification>, capacity: usize) -> Self {
+        let hub = NotificationHub::new(capacity);
+
+        let tx = hub.tx.clone();
+        _ = tokio::spawn(async move {
+            while let Some(notif) = rx.recv().await {
+                // an error only indicates no one is subscribed right now
+                _ = tx.send(notif);
+            }
+        });
+
+        hub
+    }
+
+    /// Send a notification to all current subscribers, returning how many will see it
+    pub fn send(&self, notif: Notification) -> usize {
+        self.tx.send(notif).unwrap_or(0)
+    }
+
+    /// Subscribe to notifications on the given channels
+    pub fn subscribe<I>(&self, channels: I) -> HubReceiver
+    where
+        I: IntoIterator<Item: Into<String>>,
+    {
+        HubReceiver {
+            rx: self.tx.subscribe(),
+            channels: Some(channels.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Subscribe to notifications on any channel
+    pub fn subscribe_all(&self) -> HubReceiver {
+        HubReceiver {
+            rx: self.tx.subscribe(),
+            channels: None,
+        }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+pub struct HubReceiver {
+    rx: broadcast::Receiver<Notification>,
+    channels: Option<Vec<String>>,
+}
+
+impl HubReceiver {
+    pub fn channels(&self) -> Option<&[String]> {
+        self.channels.as_deref()
+    }
+
+    /// Receive the next notification on any subscribed channel.
+    ///
+    /// If this receiver falls too far behind, the oldest notifications are skipped.
+    pub async fn recv(&mut self) -> Option<Notification> {
+        loop {
+            let notif = match self.rx.recv().await {
+                Ok(notif) => notif,
+                Err(RecvError::Closed) => return None,
+                Err(RecvError::Lagged(n)) => {
+                    log::warn!("Notification subscriber lagged behind, skipped {n} notifications");
+                    continue;
+                }
+            };
+
+            if let Some(ref channels) = self.channels {
+                if !channels.iter().any(|c| c == notif.channel()) {
+                    continue;
+                }
+            }
+
+            return Some(notif);
+        }
+    }
+}
@@ -5,20 +5,20 @@ use std::{
 
 use tokio_postgres::types::Type;
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub struct StatementCacheKeyedKey<'a> {
     pub query: Cow<'a, str>,
     pub types: Cow<'a, [Type]>,
 }
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum StatementCacheKey<'a> {
     Typed(TypeId),
     Keyed(StatementCacheKeyedKey<'a>),
 }
 
 #[repr(transparent)]
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub struct StaticStatementCacheKey(pub StatementCacheKey<'static>);
 
 impl StaticStatementCacheKey {
@@ -57,4 +57,14 @@ impl<'a> StatementCacheKey<'a> {
     pub fn typed<T: Any>() -> StatementCacheKey<'a> {
         StatementCacheKey::Typed(TypeId::of::<T>())
     }
+
+    pub fn to_static(&self) -> StaticStatementCacheKey {
+        StaticStatementCacheKey(match self {
+            StatementCacheKey::Typed(id) => StatementCacheKey::Typed(*id),
+            StatementCacheKey::Keyed(key) => StatementCacheKey::Keyed(StatementCacheKeyedKey {
+                query: Cow::Owned(key.query.clone().into_owned()),
+                types: Cow::Owned(key.types.clone().into_owned()),
+            }),
+        })
+    }
 }
@@ -3,15 +3,15 @@ pub extern crate tokio_postgres as pg;
 extern crate tracing as log;
 
 use std::{
-    collections::VecDeque,
+    collections::{BTreeSet, HashMap, VecDeque},
     ops::{Deref, DerefMut},
     pin::Pin,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, LazyLock, Weak,
     },
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use arc_swap::ArcSwap;
@@ -19,7 +19,7 @@ use arc_swap::ArcSwap;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::{
     mpsc::{self, Receiver},
-    Notify, Semaphore, TryAcquireError,
+    oneshot, Notify, Semaphore, TryAcquireError,
 };
 
 use parking_lot::{Mutex, RwLock};
@@ -27,8 +27,9 @@ use parking_lot::{Mutex, RwLock};
 use futures::{Future, Stream, StreamExt, TryFutureExt, TryStreamExt};
 
 use pg::{
+    error::DbError,
     tls::{MakeTlsConnect, TlsConnect},
-    types::{BorrowToSql, ToSql},
+    types::{BorrowToSql, ToSql, Type},
     AsyncMessage, Client as PgClient, Connection as PgConnection, Error as PgError, Notification, RowStream,
     Socket, Statement, ToStatement, Transaction as PgTransaction,
 };
@@ -37,6 +38,8 @@ pub use pg::Row;
 
 use failsafe::{futures::CircuitBreaker, Config};
 
+use stats::{Started, StatementStatsRegistry, StreamRecorder};
+
 #[inline]
 async fn timeout<O, E>(
     duration: Option<Duration>,
@@ -51,13 +54,31 @@ where
     })
 }
 
+pub mod cancel;
 pub mod config;
+pub mod credentials;
+pub mod ddl;
 pub mod error;
+pub mod hub;
+pub mod listen;
+pub mod session;
+pub mod stats;
+pub mod subscribe;
 pub mod util;
 
+pub use cancel::Canceller;
 pub use error::Error;
 
-pub use config::{PoolConfig, Timeouts};
+pub use config::{
+    ListenRecycling, NoticeHandler, NotificationMode, NotificationOverflow, PoolConfig, RecyclingMethod,
+    RetryPolicy, Timeouts,
+};
+pub use credentials::{CachedCredentials, CredentialProvider, Credentials, WithCredentials};
+pub use hub::{HubReceiver, NotificationHub};
+pub use listen::{ListenEvent, Listener};
+pub use session::SessionInfo;
+pub use stats::StatementStats;
+pub use subscribe::{FromPayload, Subscription};
 
 /// Simple wrapper type for `pg::Connection` that returns the actual message in the future
 pub struct ConnectionStream<S, T>(pub PgConnection<S, T>);
@@ -92,49 +113,292 @@ fn ro(readonly: bool) -> &'static str {
 
 use futures::stream::BoxStream;
 
+pub type SharedConnectionStream = Arc<tokio::sync::Mutex<BoxStream<'static, Result<AsyncMessage, PgError>>>>;
+
+/// Notification forwarding state of a directly driven connection
+#[derive(Default)]
+enum Forward {
+    #[default]
+    Pending,
+    Active(mpsc::Sender<Notification>),
+    /// The connection has closed, and the sender with it so that receivers observe the disconnect
+    Closed,
+}
+
+/// Notices being collected by concurrent [`Client::collect_notices`] calls, each under its own ID
+#[derive(Default)]
+struct NoticeCaptures {
+    next_id: u64,
+    active: Vec<(u64, Vec<DbError>)>,
+}
+
 #[derive(Clone)]
 pub struct Connection {
     pub readonly: bool,
     pub id: u64,
-    pub stream: Arc<tokio::sync::Mutex<BoxStream<'static, Result<AsyncMessage, PgError>>>>,
+    /// The connection stream, or `None` if the connection is being driven directly
+    pub stream: Option<SharedConnectionStream>,
     pub release: Arc<Notify>,
+    /// Where notifications are forwarded to once requested, when driven directly
+    forward: Arc<Mutex<Forward>>,
+    canceller: Canceller,
+    /// Number of notifications dropped due to the overflow policy
+    dropped_notifications: Arc<AtomicU64>,
+    notice_handler: Option<NoticeHandler>,
+    /// Notices captured by [`Client::collect_notices`]
+    captured_notices: Arc<Mutex<NoticeCaptures>>,
+    /// Overflow policy used by [`Connection::spawn_notifications`]
+    overflow: NotificationOverflow,
 }
 
 impl Connection {
+    /// Create a handle for a newly established connection, for use by a [`Connector`].
+    ///
+    /// The connection must then either be driven with [`Connection::drive`],
+    /// or given to [`Connection::with_stream`] to be polled by the notification loop.
+    pub fn new(config: &PoolConfig, canceller: Canceller) -> Connection {
+        Connection {
+            readonly: config.readonly,
+            id: ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            stream: None,
+            release: Arc::new(Notify::new()),
+            forward: Arc::default(),
+            canceller,
+            dropped_notifications: Arc::new(AtomicU64::new(0)),
+            notice_handler: config.notice_handler.clone(),
+            captured_notices: Arc::default(),
+            overflow: config.notification_overflow,
+        }
+    }
+
+    /// Set the connection stream to be polled by [`Connection::spawn_notifications`]
+    pub fn with_stream<S, T>(mut self, connection: PgConnection<S, T>) -> Connection
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        self.stream = Some(Arc::new(tokio::sync::Mutex::new(
+            ConnectionStream(connection).boxed(),
+        )));
+        self
+    }
+
+    /// Number of notifications dropped due to the overflow policy
+    pub fn dropped_notifications(&self) -> u64 {
+        self.dropped_notifications.load(Ordering::Relaxed)
+    }
+
     pub fn spawn_notifications(&self, size: usize, name_hint: Option<String>) -> Receiver<Notification> {
+        self.spawn_notifications_with(size, self.overflow, name_hint)
+    }
+
+    /// Returns true if this connection is driven directly rather than by a notification loop
+    pub fn is_direct(&self) -> bool {
+        self.stream.is_none()
+    }
+
+    fn handle_notice(&self, notice: DbError) {
+        match self.notice_handler {
+            Some(ref handler) => (handler.0)(&notice),
+            None => log::info!("Database notice: {notice}"),
+        }
+
+        for (_, captured) in &mut self.captured_notices.lock().active {
+            captured.push(notice.clone());
+        }
+    }
+
+    fn drop_notification(&self, notif: Notification) {
+        self.dropped_notifications.fetch_add(1, Ordering::Relaxed);
+        log::warn!(
+            "Dropped database notification on connection {}: {:?}",
+            self.id,
+            notif
+        );
+    }
+
+    /// Spawn a task driving the connection directly, forwarding notifications only once
+    /// [`spawn_notifications`](Self::spawn_notifications) has been called.
+    pub fn drive<S, T>(
+        &self,
+        connection: PgConnection<S, T>,
+        overflow: NotificationOverflow,
+        name_hint: String,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let this = self.clone();
+
+        _ = tokio::spawn(async move {
+            use mpsc::error::{SendTimeoutError, TrySendError};
+
+            let mut stream = ConnectionStream(connection);
+
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(AsyncMessage::Notification(notif)) => {
+                        // cloned so the lock isn't held across sends
+                        let tx = match *this.forward.lock() {
+                            Forward::Active(ref tx) => tx.clone(),
+                            _ => {
+                                log::trace!("Ignoring database notification on connection {}", this.id);
+                                continue;
+                            }
+                        };
+
+                        // if the receiver has been closed, the notification is simply discarded
+                        match overflow {
+                            NotificationOverflow::Block(None) => _ = tx.send(notif).await,
+                            NotificationOverflow::Block(Some(timeout)) => {
+                                if let Err(SendTimeoutError::Timeout(n)) =
+                                    tx.send_timeout(notif, timeout).await
+                                {
+                                    log::error!("Forwarding database notification timed out");
+                                    this.drop_notification(n);
+                                }
+                            }
+                            _ => {
+                                if let Err(TrySendError::Full(n)) = tx.try_send(notif) {
+                                    this.drop_notification(n);
+
+                                    if overflow == NotificationOverflow::Disconnect {
+                                        log::error!(
+                                            "Notification channel full on connection {}, disconnecting",
+                                            this.id
+                                        );
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Ok(AsyncMessage::Notice(notice)) => this.handle_notice(notice),
+                    Ok(_) => unreachable!("AsyncMessage is non-exhaustive"),
+                    Err(e) => {
+                        log::error!("Database connection error: {e}");
+                        break;
+                    }
+                }
+            }
+
+            *this.forward.lock() = Forward::Closed;
+
+            log::info!("Disconnected from {} database {name_hint}", ro(this.readonly));
+        });
+    }
+
+    pub fn spawn_notifications_with(
+        &self,
+        size: usize,
+        overflow: NotificationOverflow,
+        name_hint: Option<String>,
+    ) -> Receiver<Notification> {
         let (tx, rx) = mpsc::channel(size);
 
+        let Some(ref stream) = self.stream else {
+            // when driven directly, just start forwarding
+            let mut forward = self.forward.lock();
+
+            match *forward {
+                Forward::Pending => *forward = Forward::Active(tx),
+                Forward::Active(_) => log::warn!(
+                    "Notifications on connection {} are already being forwarded",
+                    self.id
+                ),
+                // `tx` is dropped, so the receiver is closed just as it would be on disconnect
+                Forward::Closed => {}
+            }
+
+            return rx;
+        };
+
+        let stream = stream.clone();
         let this = self.clone();
 
         let name_hint = name_hint.unwrap_or_else(|| "Unnamed".to_owned());
 
         _ = tokio::spawn(async move {
-            let mut stream = this.stream.lock().await;
+            use mpsc::error::{SendTimeoutError, TrySendError};
+
+            let mut stream = stream.lock().await;
+
+            // notifications that did not fit into the channel, only used with `Spill`
+            let mut pending = VecDeque::new();
+
+            let dropped = |notif: Notification| this.drop_notification(notif);
 
             let released = loop {
                 let item = tokio::select! {
                     biased;
+                    permit = tx.reserve(), if !pending.is_empty() => match permit {
+                        Ok(permit) => {
+                            permit.send(pending.pop_front().expect("pending is non-empty"));
+                            continue;
+                        }
+                        Err(_) => break false,
+                    },
                     item = stream.next() => { item }
                     _ = this.release.notified() => { break true; }
                 };
 
                 match item {
                     Some(Ok(msg)) => match msg {
-                        AsyncMessage::Notification(notif) => {
-                            use mpsc::error::SendTimeoutError;
-                            match tx.send_timeout(notif, Duration::from_secs(3)).await {
-                                Ok(_) => {}
-                                Err(SendTimeoutError::Closed(n)) => {
+                        AsyncMessage::Notification(notif) => match overflow {
+                            NotificationOverflow::Block(None) => {
+                                if let Err(e) = tx.send(notif).await {
                                     // other half has been closed, implying a drop, so exit early
-                                    log::warn!("Failed to forward database notification: {:?}", n);
+                                    log::warn!("Failed to forward database notification: {:?}", e.0);
                                     break false;
                                 }
-                                Err(SendTimeoutError::Timeout(n)) => {
-                                    log::error!("Forwarding database notification timed out: {:?}", n);
+                            }
+                            NotificationOverflow::Block(Some(timeout)) => {
+                                match tx.send_timeout(notif, timeout).await {
+                                    Ok(_) => {}
+                                    Err(SendTimeoutError::Closed(n)) => {
+                                        // other half has been closed, implying a drop, so exit early
+                                        log::warn!("Failed to forward database notification: {:?}", n);
+                                        break false;
+                                    }
+                                    Err(SendTimeoutError::Timeout(n)) => {
+                                        log::error!("Forwarding database notification timed out");
+                                        dropped(n);
+                                    }
                                 }
                             }
-                        }
-                        AsyncMessage::Notice(notice) => log::info!("Database notice: {notice}"),
+                            NotificationOverflow::Spill if !pending.is_empty() => {
+                                if pending.len() >= size {
+                                    dropped(pending.pop_front().expect("pending is non-empty"));
+                                }
+
+                                pending.push_back(notif);
+                            }
+                            _ => match tx.try_send(notif) {
+                                Ok(_) => {}
+                                Err(TrySendError::Closed(n)) => {
+                                    log::warn!("Failed to forward database notification: {:?}", n);
+                                    break false;
+                                }
+                                Err(TrySendError::Full(n)) => match overflow {
+                                    NotificationOverflow::Spill => pending.push_back(n),
+                                    NotificationOverflow::Disconnect => {
+                                        log::error!(
+                                            "Notification channel full on connection {}, disconnecting",
+                                            this.id
+                                        );
+
+                                        dropped(n);
+
+                                        // drop the underlying connection to close it
+                                        *stream = futures::stream::empty().boxed();
+
+                                        break false;
+                                    }
+                                    _ => dropped(n),
+                                },
+                            },
+                        },
+                        AsyncMessage::Notice(notice) => this.handle_notice(notice),
                         _ => unreachable!("AsyncMessage is non-exhaustive"),
                     },
                     Some(Err(e)) => {
@@ -147,6 +411,9 @@ impl Connection {
 
             drop(tx);
 
+            // anything still spilled can no longer be delivered
+            pending.into_iter().for_each(dropped);
+
             if released {
                 log::info!(
                     "Released {} connection loop to database {name_hint}",
@@ -161,6 +428,13 @@ impl Connection {
     }
 }
 
+fn listen_sql(channels: &BTreeSet<String>) -> String {
+    channels
+        .iter()
+        .map(|channel| format!("LISTEN {};", util::quote_ident(channel)))
+        .collect()
+}
+
 #[async_trait::async_trait]
 pub trait Connector {
     async fn connect(
@@ -186,7 +460,11 @@ where
 
         let circuit_breaker = Config::new().build();
 
+        let retry = &config.retry_policy;
+        let start = Instant::now();
+
         let mut attempt = 1;
+        let mut failures = 0;
         let (client, connection) = loop {
             // NOTE: This async block is not evaluated until polled, and when the circuitbreaker rejects
             // a future for rate-limiting, it is not polled, therefore this doesn't run on rejection.
@@ -201,32 +479,62 @@ where
                 config.pg_config.connect(self.clone()).await
             };
 
-            match circuit_breaker.call(connecting).await {
+            failures += 1;
+
+            let delay = match circuit_breaker.call(connecting).await {
                 Ok(res) => break res,
                 Err(failsafe::Error::Inner(e)) => {
                     log::error!("Error connecting to database {name}: {e}");
 
+                    let e = Error::from(e);
+
+                    // retrying with the same credentials won't help
+                    if e.is_auth_failure() {
+                        return Err(e);
+                    }
+
                     attempt += 1;
 
-                    if attempt > config.max_retries {
-                        return Err(e.into());
+                    let delay = retry.delay(failures);
+
+                    if attempt > config.max_retries || retry.is_exhausted(start.elapsed(), delay) {
+                        return Err(e);
                     }
+
+                    delay
                 }
                 Err(failsafe::Error::Rejected) => {
                     log::warn!("Connecting to database {name} rate-limited");
-                    tokio::time::sleep(Duration::from_secs(1)).await;
+
+                    let delay = retry.delay(failures);
+
+                    if retry.is_exhausted(start.elapsed(), delay) {
+                        return Err(Error::ConnectionFailure);
+                    }
+
+                    delay
                 }
-            }
-        };
+            };
 
-        let conn = Connection {
-            readonly: config.readonly,
-            id: ID_COUNTER.fetch_add(1, Ordering::Relaxed),
-            stream: Arc::new(tokio::sync::Mutex::new(ConnectionStream(connection).boxed())),
-            release: Arc::new(Notify::new()),
+            tokio::time::sleep(delay).await;
         };
 
-        let rx = conn.spawn_notifications(config.channel_size, Some(name.to_owned()));
+        let conn = Connection::new(config, cancel::canceller(self.clone()));
+
+        if config.notification_mode == NotificationMode::OnDemand {
+            conn.drive(connection, config.notification_overflow, name.to_owned());
+
+            // forwarding is set up on demand, so start with a closed receiver
+            return Ok((client, conn, mpsc::channel(1).1));
+        }
+
+        let conn = conn.with_stream(connection);
+
+        let rx = conn.spawn_notifications_with(
+            config.channel_size,
+            config.notification_overflow,
+            Some(name.to_owned()),
+        );
 
         Ok((client, conn, rx))
     }
@@ -236,11 +544,24 @@ pub struct PoolInner {
     config: ArcSwap<PoolConfig>,
     connector: Box<dyn Connector + Send + Sync + 'static>,
     queue: Mutex<VecDeque<Client>>,
+    /// Idle clients with active subscriptions, set aside by `ListenRecycling::Preserve`
+    listeners: Mutex<VecDeque<Client>>,
     semaphore: Semaphore,
+    /// Statements prepared on every new connection before it's handed out
+    prewarm: RwLock<Vec<PrewarmStatement>>,
+    /// Per-statement execution statistics, shared with every client
+    stmt_stats: Arc<StatementStatsRegistry>,
 
     pub stmt_caches: StatementCaches,
 }
 
+#[derive(Clone)]
+struct PrewarmStatement {
+    key: StaticStatementCacheKey,
+    sql: String,
+    types: Vec<Type>,
+}
+
 #[derive(Clone)]
 pub struct Pool(Arc<PoolInner>);
 
@@ -262,7 +583,10 @@ impl Pool {
             semaphore: Semaphore::new(config.max_connections),
             connector: Box::new(conn),
             queue: Mutex::new(VecDeque::with_capacity(config.max_connections)),
+            listeners: Mutex::new(VecDeque::new()),
+            prewarm: RwLock::new(Vec::new()),
             stmt_caches: StatementCaches::default(),
+            stmt_stats: Arc::default(),
             config: ArcSwap::from_pointee(config),
         }))
     }
@@ -274,6 +598,9 @@ impl Pool {
             self.config.store(Arc::new(config));
             // TODO: Figure out how semaphore should be updated
             queue.clear();
+            drop(queue);
+
+            self.listeners.lock().clear();
         }
     }
 
@@ -282,20 +609,113 @@ impl Pool {
 
         let (client, conn, rx) = self.connector.connect(&config).await?;
 
-        let stmt_cache = Arc::new(StatementCache::default());
-        self.stmt_caches.attach(&stmt_cache);
+        if let Some(sql) = config.session_sql() {
+            if config.transaction_pooling {
+                log::warn!("Session parameters are ignored with transaction pooling");
+            } else {
+                client.simple_query(&sql).await?;
+            }
+        }
+
+        let session = SessionInfo::fetch(&client).await?;
+
+        log::debug!(
+            "Connection {} established with backend PID {}",
+            conn.id,
+            session.backend_pid
+        );
+
+        let stmt_cache = self.stmt_caches.create(conn.id, config.statement_cache_size);
+
+        self.prewarm_statements(&client, &stmt_cache, config.readonly)
+            .await;
 
         Ok(Client {
             readonly: config.readonly,
             config,
             client,
             rx,
+            hub: None,
+            listening: BTreeSet::new(),
+            unlisten_on_recycle: false,
             conn,
+            session,
             stmt_cache,
+            stmt_stats: self.stmt_stats.clone(),
+            checkout_wait: AtomicU64::new(0),
+            health: Arc::default(),
         })
     }
 
-    async fn recycle(&self, client: &Client) -> Result<(), Error> {
+    /// Client-side execution statistics of every statement run through the statement cache or as a thorn query.
+    ///
+    /// Statements are tracked until [`Pool::reset_statement_stats`] is called, so dynamic SQL will accumulate entries.
+    pub fn statement_stats(&self) -> Vec<StatementStats> {
+        self.stmt_stats.snapshot()
+    }
+
+    pub fn reset_statement_stats(&self) {
+        self.stmt_stats.clear();
+    }
+
+    /// Register a statement to be prepared on every new connection before it's handed out
+    pub fn prewarm(&self, sql: impl Into<String>, types: &[Type]) {
+        let sql = sql.into();
+
+        self.prewarm.write().push(PrewarmStatement {
+            key: StaticStatementCacheKey::owned(sql.clone(), types.to_vec()),
+            sql,
+            types: types.to_vec(),
+        });
+    }
+
+    /// Register a thorn query to be prepared on every new connection before it's handed out
+    pub fn prewarm_query<E: RowColumns>(&self, query: &Query<'_, E>) {
+        let (key, sql, types) = match query.cached {
+            Some(cached) => (StaticStatementCacheKey::typed::<E>(), &cached.q, &cached.params),
+            None => (
+                StaticStatementCacheKey::owned(query.q.clone(), query.param_tys.clone()),
+                &query.q,
+                &query.param_tys,
+            ),
+        };
+
+        self.prewarm.write().push(PrewarmStatement {
+            key,
+            sql: sql.clone(),
+            types: types.clone(),
+        });
+    }
+
+    async fn prewarm_statements(&self, client: &PgClient, stmt_cache: &StatementCache, readonly: bool) {
+        let statements = self.prewarm.read().clone();
+
+        if statements.is_empty() || self.config.load().transaction_pooling {
+            return;
+        }
+
+        // requests are pipelined by tokio-postgres when polled concurrently
+        let prepared = futures::future::join_all(statements.iter().map(|s| async move {
+            let start = Instant::now();
+            let res = client
+                .prepare_typed(check_readonly(&s.sql, readonly), &s.types)
+                .await;
+            (res, start.elapsed())
+        }))
+        .await;
+
+        for (s, (res, elapsed)) in statements.into_iter().zip(prepared) {
+            match res {
+                Ok(stmt) => {
+                    stmt_cache.metrics().record_prepare(elapsed);
+                    stmt_cache.set_with_sql(s.key, stmt, &s.sql);
+                }
+                Err(e) => log::warn!("Error pre-warming statement \"{}\": {e}", s.sql),
+            }
+        }
+    }
+
+    async fn recycle(&self, client: &mut Client) -> Result<(), Error> {
         if client.client.is_closed() {
             log::info!(
                 "Connection {} could not be recycled because it was closed",
@@ -304,13 +724,66 @@ impl Pool {
             return Err(Error::RecyclingError);
         }
 
-        if let Some(sql) = self.config.load().recycling_method.query() {
+        if client.is_poisoned() {
+            log::info!(
+                "Connection {} could not be recycled because it was poisoned",
+                client.conn.id
+            );
+            return Err(Error::RecyclingError);
+        }
+
+        let recycling_method = self.config.load().effective_recycling_method();
+
+        if let Some(sql) = recycling_method.query() {
             if let Err(e) = client.client.simple_query(sql).await {
                 log::warn!("Connection could not be recycled: {e}");
                 return Err(Error::RecyclingError);
             }
         }
 
+        if recycling_method.resets_session() {
+            if let Some(sql) = client.config.session_sql() {
+                if let Err(e) = client.client.simple_query(&sql).await {
+                    log::warn!("Connection session could not be re-initialized: {e}");
+                    return Err(Error::RecyclingError);
+                }
+            }
+        }
+
+        if !client.listening.is_empty() {
+            let listen_recycling = match std::mem::take(&mut client.unlisten_on_recycle) {
+                true => ListenRecycling::Reset,
+                false => client.config.listen_recycling,
+            };
+
+            let sql = match listen_recycling {
+                ListenRecycling::Preserve if recycling_method.resets_session() => {
+                    Some(listen_sql(&client.listening))
+                }
+                ListenRecycling::Preserve => None,
+                ListenRecycling::Reset => {
+                    client.listening.clear();
+
+                    match recycling_method.resets_session() {
+                        true => None,
+                        false => Some("UNLISTEN *".to_owned()),
+                    }
+                }
+            };
+
+            if let Some(sql) = sql {
+                if let Err(e) = client.client.simple_query(&sql).await {
+                    log::warn!("Connection subscriptions could not be recycled: {e}");
+                    return Err(Error::RecyclingError);
+                }
+            }
+        }
+
+        if client.listening.is_empty() {
+            // discard any stale notifications so they aren't seen by the next user
+            while client.rx.try_recv().is_ok() {}
+        }
+
         Ok(())
     }
 
@@ -325,6 +798,67 @@ impl Pool {
     }
 
     pub async fn timeout_get(&self, timeouts: &Timeouts) -> Result<Object, Error> {
+        self.timeout_get_from(timeouts, || self.pop_idle()).await
+    }
+
+    /// Get a client subscribed to exactly the given channels, preferring to reuse one set aside
+    /// by [`ListenRecycling::Preserve`] so that no pending notifications are lost.
+    pub async fn get_listening<I>(&self, channels: I) -> Result<Object, Error>
+    where
+        I: IntoIterator<Item: Into<String>>,
+    {
+        let channels: BTreeSet<String> = channels.into_iter().map(Into::into).collect();
+
+        let config = self.config.load();
+
+        if config.transaction_pooling {
+            return Err(Error::Unsupported(listen::LISTEN_UNSUPPORTED));
+        }
+
+        let timeouts = config.timeouts;
+
+        let mut client = self
+            .timeout_get_from(&timeouts, || {
+                let mut listeners = self.listeners.lock();
+
+                match listeners.iter().position(|c| c.listening == channels) {
+                    Some(idx) => listeners.remove(idx),
+                    None => {
+                        drop(listeners);
+                        self.pop_idle()
+                    }
+                }
+            })
+            .await?;
+
+        for channel in &channels {
+            if !client.listening.contains(channel) {
+                client.listen(channel).await?;
+            }
+        }
+
+        Ok(client)
+    }
+
+    /// Pop an idle client, falling back to the longest-parked listener so that
+    /// connections set aside by [`ListenRecycling::Preserve`] still count against `max_connections`
+    fn pop_idle(&self) -> Option<Client> {
+        if let Some(client) = self.queue.lock().pop_front() {
+            return Some(client);
+        }
+
+        let mut client = self.listeners.lock().pop_front()?;
+        client.unlisten_on_recycle = true;
+        Some(client)
+    }
+
+    async fn timeout_get_from(
+        &self,
+        timeouts: &Timeouts,
+        mut pop: impl FnMut() -> Option<Client>,
+    ) -> Result<Object, Error> {
+        let start = Instant::now();
+
         let mut client = Object {
             inner: None,
             state: State::Waiting,
@@ -350,17 +884,14 @@ impl Pool {
         loop {
             client.state = State::Receiving;
 
-            let inner_client = {
-                let mut queue = self.queue.lock();
-                queue.pop_front()
-            };
+            let inner_client = pop();
 
             match inner_client {
                 Some(inner_client) => {
                     client.state = State::Recycling;
                     client.inner = Some(inner_client);
 
-                    match timeout(timeouts.recycle, self.recycle(&client)).await {
+                    match timeout(timeouts.recycle, self.recycle(&mut client)).await {
                         Ok(_) => break,
 
                         // Note that in this case the `client` is reused
@@ -370,7 +901,11 @@ impl Pool {
                 }
                 None => {
                     client.state = State::Creating;
-                    client.inner = Some(timeout(timeouts.create, self.create()).await?);
+                    let created = self.spawn_create(timeouts.create).await?;
+
+                    // the permit is handed over along with the client
+                    client.inner = Some(created.take());
+                    client.state = State::Ready;
 
                     break;
                 }
@@ -379,43 +914,136 @@ impl Pool {
 
         client.state = State::Ready;
 
+        if let Some(ref inner) = client.inner {
+            inner
+                .checkout_wait
+                .store(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        }
+
         Ok(client)
     }
 
+    /// Create a new client in the background, so that if the caller is cancelled
+    /// the connection is still established and parked in the queue for the next caller.
+    ///
+    /// The task takes over the caller's permit, handing it back along with the client or
+    /// returning it to the pool if creation fails. This keeps abandoned creates counted
+    /// against `max_connections` until they complete.
+    async fn spawn_create(&self, create_timeout: Option<Duration>) -> Result<Created, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        let pool = self.clone();
+        tokio::spawn(async move {
+            match timeout(create_timeout, pool.create()).await {
+                // if the caller is gone, the `Created` is dropped, parking the client
+                Ok(client) => {
+                    _ = tx.send(Ok(Created {
+                        client: Some(client),
+                        pool: Arc::downgrade(&pool.0),
+                    }))
+                }
+                Err(e) => {
+                    pool.semaphore.add_permits(1);
+                    _ = tx.send(Err(e));
+                }
+            }
+        });
+
+        rx.await.map_err(|_| Error::ConnectionFailure)?
+    }
+
     pub async fn close(&self) {
         self.semaphore.close();
         self.queue.lock().clear();
+        self.listeners.lock().clear();
     }
 }
 
-/// Set of `StatementCache`s. This exists to allow for clearing all caches at once.
+/// Set of `StatementCache`s, keyed by connection ID. This exists to allow for clearing all caches at once.
+///
+/// Caches of connections that have since been dropped are pruned whenever a cache is attached or detached.
 #[derive(Default)]
 pub struct StatementCaches {
-    caches: RwLock<Vec<Weak<StatementCache>>>,
+    caches: RwLock<HashMap<u64, Weak<StatementCache>, foldhash::fast::RandomState>>,
+    metrics: Arc<StatementCacheMetrics>,
 }
 
 impl StatementCaches {
+    /// Create a new cache for the given connection, sharing this set's metrics, and attach it
+    pub fn create(&self, id: u64, capacity: Option<usize>) -> Arc<StatementCache> {
+        let cache = Arc::new(StatementCache {
+            id,
+            capacity,
+            metrics: self.metrics.clone(),
+            ..StatementCache::default()
+        });
+
+        self.attach(&cache);
+
+        cache
+    }
+
     pub fn attach(&self, cache: &Arc<StatementCache>) {
-        let cache = Arc::downgrade(cache);
-        self.caches.write().push(cache);
+        let mut caches = self.caches.write();
+        caches.retain(|_, sc| sc.strong_count() > 0);
+        caches.insert(cache.id, Arc::downgrade(cache));
     }
 
     pub fn detach(&self, cache: &Arc<StatementCache>) {
-        let cache = Arc::downgrade(cache);
-        self.caches.write().retain(|sc| !sc.ptr_eq(&cache));
+        let mut caches = self.caches.write();
+        caches.retain(|&id, sc| id != cache.id && sc.strong_count() > 0);
+    }
+
+    /// Get the cache of a specific connection, if it's still alive
+    pub fn get(&self, id: u64) -> Option<Arc<StatementCache>> {
+        self.caches.read().get(&id).and_then(Weak::upgrade)
+    }
+
+    /// Clear the cache of a specific connection, returning false if it could not be found
+    pub fn clear_connection(&self, id: u64) -> bool {
+        match self.get(id) {
+            Some(cache) => {
+                cache.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// IDs of connections with live caches
+    pub fn connection_ids(&self) -> Vec<u64> {
+        let caches = self.caches.read();
+        caches
+            .iter()
+            .filter(|(_, sc)| sc.strong_count() > 0)
+            .map(|(&id, _)| id)
+            .collect()
     }
 
     pub fn clear(&self) {
         let caches = self.caches.read();
-        for cache in caches.iter() {
+        for cache in caches.values() {
             if let Some(cache) = cache.upgrade() {
                 cache.clear();
             }
         }
     }
 
+    /// Metrics aggregated across every cache created through [`StatementCaches::create`]
+    pub fn stats(&self) -> StatementCacheStats {
+        let size = self
+            .caches
+            .read()
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|cache| cache.len())
+            .sum();
+
+        self.metrics.snapshot(size)
+    }
+
     pub fn cleanup(&self) {
-        self.caches.write().retain(|sc| sc.strong_count() > 0)
+        self.caches.write().retain(|_, sc| sc.strong_count() > 0)
     }
 }
 
@@ -430,6 +1058,44 @@ enum State {
     Dropped,
 }
 
+/// A new client on its way from [`Pool::spawn_create`] to the caller, holding a permit.
+///
+/// If dropped without being taken, such as when the caller was cancelled before receiving it,
+/// the client is parked in the queue for the next caller and the permit is returned.
+struct Created {
+    client: Option<Client>,
+    pool: Weak<PoolInner>,
+}
+
+impl Created {
+    /// Take the client, along with responsibility for its permit
+    fn take(mut self) -> Client {
+        self.client.take().expect("Double-take of created client")
+    }
+}
+
+impl Drop for Created {
+    fn drop(&mut self) {
+        let Some(client) = self.client.take() else {
+            return;
+        };
+
+        let Some(pool) = self.pool.upgrade() else {
+            return;
+        };
+
+        if !pool.semaphore.is_closed() && Arc::ptr_eq(&client.config, &pool.config.load()) {
+            log::debug!(
+                "Parking connection {} after checkout was cancelled",
+                client.conn.id
+            );
+            pool.queue.lock().push_back(client);
+        }
+
+        pool.semaphore.add_permits(1);
+    }
+}
+
 pub struct Object {
     inner: Option<Client>,
     pool: Weak<PoolInner>,
@@ -482,15 +1148,38 @@ impl Drop for Object {
     fn drop(&mut self) {
         if let Some(pool) = self.pool.upgrade() {
             match self.state {
-                State::Waiting | State::Dropped => { /*no-op*/ }
-                State::Receiving | State::Creating | State::Taken => pool.semaphore.add_permits(1),
+                // while creating, the permit is owned by the background task, see `Pool::spawn_create`
+                State::Waiting | State::Creating | State::Dropped => { /*no-op*/ }
+                State::Receiving | State::Taken => pool.semaphore.add_permits(1),
                 State::Recycling | State::Ready => {
                     let client = self.inner.take().expect("Double-take of dropped client");
 
-                    // if still using the same config, allow reuse of this connection
-                    if Arc::ptr_eq(&client.config, &pool.config.load()) {
-                        let mut queue = pool.queue.lock();
-                        queue.push_back(client);
+                    if client.is_poisoned() {
+                        log::info!("Discarding poisoned connection {}", client.conn.id);
+                    } else if Arc::ptr_eq(&client.config, &pool.config.load()) {
+                        // still using the same config, so allow reuse of this connection
+                        if client.listening.is_empty()
+                            || client.config.listen_recycling != ListenRecycling::Preserve
+                        {
+                            pool.queue.lock().push_back(client);
+                        } else {
+                            let max_idle_listeners = pool.config.load().max_idle_listeners;
+
+                            let mut listeners = pool.listeners.lock();
+                            listeners.push_back(client);
+
+                            // too many set aside, so reset the oldest and return it to general use
+                            let excess = listeners.len().saturating_sub(max_idle_listeners);
+                            let evicted: Vec<Client> = listeners.drain(..excess).collect();
+
+                            // release before touching the queue, as the two locks are never held together
+                            drop(listeners);
+
+                            for mut evicted in evicted {
+                                evicted.unlisten_on_recycle = true;
+                                pool.queue.lock().push_back(evicted);
+                            }
+                        }
                     }
 
                     // even if we didn't add this client back into the queue,
@@ -509,34 +1198,292 @@ pub mod key;
 
 use key::{StatementCacheKey, StaticStatementCacheKey};
 
+/// Statement cache counters, shared by every cache in a pool
 #[derive(Default)]
-pub struct StatementCache {
-    cache: scc::HashMap<StaticStatementCacheKey, Statement, foldhash::fast::RandomState>,
+pub struct StatementCacheMetrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    prepares: AtomicU64,
+    prepare_nanos: AtomicU64,
+    evictions: AtomicU64,
 }
 
-impl StatementCache {
-    pub fn get(&self, key: &StatementCacheKey) -> Option<Statement> {
-        self.cache.read(key, |_k, v| v.clone())
+impl StatementCacheMetrics {
+    pub fn record_prepare(&self, elapsed: Duration) {
+        self.prepares.fetch_add(1, Ordering::Relaxed);
+        self.prepare_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
     }
 
-    pub fn set(&self, key: StaticStatementCacheKey, stmt: Statement) {
-        _ = self.cache.entry(key).insert_entry(stmt);
+    pub fn snapshot(&self, size: usize) -> StatementCacheStats {
+        StatementCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            prepares: self.prepares.load(Ordering::Relaxed),
+            prepare_time: Duration::from_nanos(self.prepare_nanos.load(Ordering::Relaxed)),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            size,
+        }
     }
+}
 
-    pub fn clear(&self) {
-        self.cache.clear();
+/// Snapshot of [`StatementCacheMetrics`]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub prepares: u64,
+    /// Total time spent preparing statements
+    pub prepare_time: Duration,
+    pub evictions: u64,
+    /// Number of statements currently cached
+    pub size: usize,
+}
+
+impl StatementCacheStats {
+    /// Average time spent preparing a statement
+    pub fn mean_prepare_time(&self) -> Duration {
+        match self.prepares {
+            0 => Duration::ZERO,
+            n => self.prepare_time / n.min(u32::MAX as u64) as u32,
+        }
     }
 }
 
-pub struct Client {
-    readonly: bool,
-    client: PgClient,
-    config: Arc<PoolConfig>,
-    conn: Connection,
-    rx: Receiver<Notification>,
+struct CachedStatement {
+    stmt: Statement,
+    /// Reference bit for CLOCK eviction, set on every hit
+    referenced: AtomicBool,
+    /// SQL the statement was prepared from, kept to detect collisions between typed keys
+    #[cfg(debug_assertions)]
+    sql: Option<Box<str>>,
+}
+
+/// Per-connection cache of prepared statements, optionally bounded in size.
+///
+/// When full, entries are evicted using a CLOCK (second-chance) policy,
+/// and dropping the evicted `Statement` deallocates it on the server.
+/// A capacity of zero disables caching entirely.
+#[derive(Default)]
+pub struct StatementCache {
+    /// ID of the connection this cache belongs to
+    id: u64,
+    cache: scc::HashMap<StaticStatementCacheKey, CachedStatement, foldhash::fast::RandomState>,
+    capacity: Option<usize>,
+    /// Keys in CLOCK order with the hand at the front, only maintained when bounded.
+    /// Also serializes bounded inserts so concurrent misses can't overshoot the capacity.
+    clock: Mutex<VecDeque<StaticStatementCacheKey>>,
+    evictions: AtomicU64,
+    metrics: Arc<StatementCacheMetrics>,
+}
+
+impl StatementCache {
+    pub fn new(id: u64, capacity: Option<usize>) -> Self {
+        StatementCache {
+            id,
+            capacity,
+            ..StatementCache::default()
+        }
+    }
+
+    pub fn get(&self, key: &StatementCacheKey) -> Option<Statement> {
+        let res = self.cache.read(key, |_k, v| {
+            v.referenced.store(true, Ordering::Relaxed);
+            v.stmt.clone()
+        });
+
+        match res {
+            Some(_) => &self.metrics.hits,
+            None => &self.metrics.misses,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+
+        res
+    }
+
+    pub fn set(&self, key: StaticStatementCacheKey, stmt: Statement) {
+        self.insert(key, stmt, None);
+    }
+
+    /// Like [`StatementCache::set`], but in debug builds also records the SQL text,
+    /// so that hits on typed keys can be checked with [`StatementCache::cached_sql`].
+    pub fn set_with_sql(&self, key: StaticStatementCacheKey, stmt: Statement, sql: &str) {
+        self.insert(key, stmt, Some(sql));
+    }
+
+    /// SQL text recorded by [`StatementCache::set_with_sql`], only available in debug builds
+    #[cfg(debug_assertions)]
+    pub fn cached_sql(&self, key: &StatementCacheKey) -> Option<String> {
+        self.cache
+            .read(key, |_k, v| v.sql.as_deref().map(str::to_owned))
+            .flatten()
+    }
+
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn insert(&self, key: StaticStatementCacheKey, stmt: Statement, sql: Option<&str>) {
+        let entry = CachedStatement {
+            stmt,
+            referenced: AtomicBool::new(false),
+            #[cfg(debug_assertions)]
+            sql: sql.map(Box::from),
+        };
+
+        let Some(capacity) = self.capacity else {
+            _ = self.cache.entry(key).insert_entry(entry);
+            return;
+        };
+
+        if capacity == 0 {
+            return;
+        }
+
+        let mut clock = self.clock.lock();
+
+        if self.cache.contains(&key) {
+            _ = self.cache.entry(key).insert_entry(entry);
+            return;
+        }
+
+        while self.cache.len() >= capacity && self.evict_one(&mut clock) {}
+
+        clock.push_back(key.clone());
+        _ = self.cache.entry(key).insert_entry(entry);
+    }
+
+    /// Advance the hand until an entry not referenced since it last passed is found and evicted,
+    /// giving referenced entries a second chance by clearing their bit and moving them behind the hand.
+    fn evict_one(&self, clock: &mut VecDeque<StaticStatementCacheKey>) -> bool {
+        while let Some(key) = clock.pop_front() {
+            let referenced = self
+                .cache
+                .read(&key, |_k, v| v.referenced.swap(false, Ordering::Relaxed));
+
+            match referenced {
+                Some(true) => clock.push_back(key),
+                Some(false) => {
+                    self.cache.remove(&key);
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.evictions.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                }
+                // removed from the cache directly, so just drop it from the clock
+                None => {}
+            }
+        }
+
+        false
+    }
+
+    pub fn remove(&self, key: &StatementCacheKey) -> bool {
+        if self.capacity.is_some() {
+            self.clock.lock().retain(|k| k.0 != *key);
+        }
+
+        self.cache.remove(key).is_some()
+    }
+
+    pub fn clear(&self) {
+        let mut clock = self.clock.lock();
+        clock.clear();
+        self.cache.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Number of statements evicted to stay within capacity
+    pub fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    pub fn metrics(&self) -> &StatementCacheMetrics {
+        &self.metrics
+    }
+}
+
+/// Tracks whether a connection is still safe to hand out again, shared between a client and its transactions
+#[derive(Debug, Default)]
+pub(crate) struct Health {
+    poisoned: AtomicBool,
+}
+
+impl Health {
+    pub fn poison(&self) {
+        self.poisoned.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Poison the connection if `err` has left it unusable
+    pub fn observe(&self, err: &Error) {
+        if err.is_fatal() {
+            log::debug!("Poisoning connection after fatal error: {err}");
+            self.poison();
+        }
+    }
+
+    pub fn fail(&self, err: PgError) -> Error {
+        let err = Error::from(err);
+        self.observe(&err);
+        err
+    }
+
+    /// Mark a statement as in-flight, poisoning the connection if it's dropped before [`InFlight::finish`]
+    pub fn begin(&self) -> InFlight<'_> {
+        InFlight(Some(self))
+    }
+}
+
+pub(crate) struct InFlight<'a>(Option<&'a Health>);
+
+impl InFlight<'_> {
+    pub fn finish(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(health) = self.0 {
+            log::debug!("Poisoning connection after a statement was abandoned mid-flight");
+            health.poison();
+        }
+    }
+}
+
+pub struct Client {
+    readonly: bool,
+    client: PgClient,
+    config: Arc<PoolConfig>,
+    conn: Connection,
+    rx: Receiver<Notification>,
+    hub: Option<NotificationHub>,
+    listening: BTreeSet<String>,
+    /// Set when a parked listener is handed out for general use, so recycling unsubscribes it
+    unlisten_on_recycle: bool,
+    session: SessionInfo,
 
     // NOTE: This is an Arc to allow cloning it to transactions without needing a ref
     pub(crate) stmt_cache: Arc<StatementCache>,
+    stmt_stats: Arc<StatementStatsRegistry>,
+    /// Nanoseconds spent waiting on the pool for this client, until taken by the next statement
+    checkout_wait: AtomicU64,
+    health: Arc<Health>,
 }
 
 impl AsRef<PgClient> for Client {
@@ -550,24 +1497,218 @@ pub struct Transaction<'a> {
     id: u64,
     stmt_cache: Arc<StatementCache>,
     readonly: bool,
+    canceller: Canceller,
+    query_timeout: Option<Duration>,
+    /// Execute thorn queries as unnamed statements, see `PoolConfig::transaction_pooling`
+    unnamed: bool,
+    stmt_stats: Arc<StatementStatsRegistry>,
+    checkout_wait: &'a AtomicU64,
+    health: Arc<Health>,
 }
 
 impl Client {
+    /// Unique ID of the underlying connection, see [`Connection::id`]
+    pub fn id(&self) -> u64 {
+        self.conn.id
+    }
+
+    /// Prepared statement cache of this connection
+    pub fn statement_cache(&self) -> &StatementCache {
+        &self.stmt_cache
+    }
+
+    /// Mark this connection as unsafe to reuse, so it's discarded rather than returned to the pool.
+    ///
+    /// This happens automatically after fatal errors or statements abandoned mid-flight, but should be done
+    /// manually if the connection is left in an unknown state through [`AsRef<PgClient>`], such as a partial `COPY`.
+    pub fn poison(&self) {
+        self.health.poison();
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.health.is_poisoned()
+    }
+
+    /// Server session information captured when the connection was established
+    pub fn session_info(&self) -> &SessionInfo {
+        &self.session
+    }
+
+    /// Process ID of the backend serving this connection, useful for correlating with `pg_stat_activity`
+    pub fn backend_pid(&self) -> i32 {
+        self.session.backend_pid
+    }
+
+    /// Stop the notification loop and take the connection, allowing a new loop to be spawned.
+    ///
+    /// If the connection is driven directly, it will continue to be driven.
     pub async fn take_connection(&self) -> Connection {
-        self.conn.release.notify_one();
-        drop(self.conn.stream.lock().await);
+        if let Some(ref stream) = self.conn.stream {
+            self.conn.release.notify_one();
+            drop(stream.lock().await);
+        }
+
         self.conn.clone()
     }
 
+    /// Start forwarding notifications to this client if the connection is driven directly
+    /// and they aren't already. This is done automatically by [`Client::listen`].
+    pub fn enable_notifications(&mut self) {
+        if self.conn.is_direct()
+            && matches!(*self.conn.forward.lock(), Forward::Pending)
+            && self.hub.is_none()
+        {
+            self.rx = self.conn.spawn_notifications_with(
+                self.config.channel_size,
+                self.config.notification_overflow,
+                None,
+            );
+        }
+    }
+
+    /// Number of notifications dropped on this connection due to the overflow policy
+    pub fn dropped_notifications(&self) -> u64 {
+        self.conn.dropped_notifications.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel_token(&self) -> pg::CancelToken {
+        self.client.cancel_token()
+    }
+
+    /// Run `future` with the given timeout, cancelling the running query on the server
+    /// if the timeout elapses or the returned future is dropped before completion.
+    pub async fn cancellable<O>(
+        &self,
+        timeout: Option<Duration>,
+        future: impl Future<Output = Result<O, Error>>,
+    ) -> Result<O, Error> {
+        let canceller = self.conn.canceller.clone();
+
+        cancel::cancellable(self.cancel_token(), canceller, &self.health, timeout, future).await
+    }
+
+    /// Run `future`, collecting any notices the database sends while it runs.
+    ///
+    /// Notices are not tied to a specific query, so any other queries running
+    /// concurrently on this connection may also contribute notices.
+    pub async fn collect_notices<F: Future>(&self, future: F) -> (F::Output, Vec<DbError>) {
+        struct Capture<'a> {
+            notices: &'a Mutex<NoticeCaptures>,
+            id: u64,
+        }
+
+        impl Capture<'_> {
+            /// Stop capturing, returning what was collected
+            fn take(&self) -> Vec<DbError> {
+                let mut notices = self.notices.lock();
+
+                match notices.active.iter().position(|(id, _)| *id == self.id) {
+                    Some(idx) => notices.active.swap_remove(idx).1,
+                    None => Vec::new(),
+                }
+            }
+        }
+
+        impl Drop for Capture<'_> {
+            fn drop(&mut self) {
+                self.take();
+            }
+        }
+
+        let capture = {
+            let mut notices = self.conn.captured_notices.lock();
+
+            let id = notices.next_id;
+            notices.next_id += 1;
+            notices.active.push((id, Vec::new()));
+
+            Capture {
+                notices: &self.conn.captured_notices,
+                id,
+            }
+        };
+
+        let res = future.await;
+
+        let notices = capture.take();
+
+        (res, notices)
+    }
+
+    /// Receive the next notification on this connection.
+    ///
+    /// Returns `None` once the notifications have been handed over to a [`NotificationHub`] or [`Subscription`].
     pub async fn recv_notif(&mut self) -> Option<Notification> {
         self.rx.recv().await
     }
 
+    /// Subscribe to notifications on the given channel, tracking it for recycling
+    pub async fn listen(&mut self, channel: &str) -> Result<(), Error> {
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(listen::LISTEN_UNSUPPORTED));
+        }
+
+        self.enable_notifications();
+
+        self.client
+            .batch_execute(&format!("LISTEN {}", util::quote_ident(channel)))
+            .await?;
+        self.listening.insert(channel.to_owned());
+        Ok(())
+    }
+
+    pub async fn unlisten(&mut self, channel: &str) -> Result<(), Error> {
+        self.client
+            .batch_execute(&format!("UNLISTEN {}", util::quote_ident(channel)))
+            .await?;
+        self.listening.remove(channel);
+        Ok(())
+    }
+
+    pub async fn unlisten_all(&mut self) -> Result<(), Error> {
+        self.client.batch_execute("UNLISTEN *").await?;
+        self.listening.clear();
+        Ok(())
+    }
+
+    /// Channels subscribed to through [`Client::listen`]
+    pub fn listening(&self) -> impl Iterator<Item = &str> {
+        self.listening.iter().map(String::as_str)
+    }
+
+    /// Hand notifications over to a broadcast hub, allowing for many subscribers.
+    /// Afterwards, [`Client::recv_notif`] will return `None`.
+    ///
+    /// As with [`Client::subscribe`], the connection is closed rather than returned to the pool
+    /// once dropped, so that its notifications never reach subscribers from another checkout.
+    pub fn notification_hub(&mut self) -> NotificationHub {
+        if let Some(ref hub) = self.hub {
+            return hub.clone();
+        }
+
+        self.enable_notifications();
+
+        // replace with a receiver that's already closed
+        let rx = std::mem::replace(&mut self.rx, mpsc::channel(1).1);
+
+        self.poison();
+
+        self.hub
+            .insert(NotificationHub::forward(rx, self.config.channel_size))
+            .clone()
+    }
+
     pub async fn transaction(&mut self) -> Result<Transaction<'_>, Error> {
         Ok(Transaction {
             readonly: self.readonly,
             id: self.conn.id,
             stmt_cache: self.stmt_cache.clone(),
+            canceller: self.conn.canceller.clone(),
+            query_timeout: self.config.timeouts.query,
+            unnamed: self.config.transaction_pooling,
+            stmt_stats: self.stmt_stats.clone(),
+            checkout_wait: &self.checkout_wait,
+            health: self.health.clone(),
             t: self.client.transaction().await?,
         })
     }
@@ -608,10 +1749,19 @@ impl Client {
         I: IntoIterator<Item = P>,
         I::IntoIter: ExactSizeIterator,
     {
-        self.client
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        // the outcome is unknown if this is dropped before completion
+        let in_flight = self.health.begin();
+        let res = self
+            .client
             .query_raw(statement, params)
             .await
-            .map_err(Error::from)
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query_stream<T>(
@@ -638,24 +1788,54 @@ impl Client {
     where
         T: ?Sized + ToStatement,
     {
-        self.client.execute(statement, params).await.map_err(Error::from)
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        let in_flight = self.health.begin();
+        let res = self
+            .client
+            .execute(statement, params)
+            .await
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>, Error>
     where
         T: ?Sized + ToStatement,
     {
-        self.client.query(statement, params).await.map_err(Error::from)
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        let in_flight = self.health.begin();
+        let res = self
+            .client
+            .query(statement, params)
+            .await
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query_one<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<Row, Error>
     where
         T: ?Sized + ToStatement,
     {
-        self.client
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        let in_flight = self.health.begin();
+        let res = self
+            .client
             .query_one(statement, params)
             .await
-            .map_err(Error::from)
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query_opt<T>(
@@ -666,20 +1846,84 @@ impl Client {
     where
         T: ?Sized + ToStatement,
     {
-        self.client
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        let in_flight = self.health.begin();
+        let res = self
+            .client
             .query_opt(statement, params)
             .await
-            .map_err(Error::from)
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
+    }
+
+    pub async fn execute_with_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.config.timeouts.query, self.execute(statement, params))
+            .await
+    }
+
+    pub async fn query_with_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.config.timeouts.query, self.query(statement, params))
+            .await
+    }
+
+    pub async fn query_one_with_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Row, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.config.timeouts.query, self.query_one(statement, params))
+            .await
+    }
+
+    pub async fn query_opt_with_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Option<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.config.timeouts.query, self.query_opt(statement, params))
+            .await
     }
 }
 
 impl Transaction<'_> {
     pub async fn commit(self) -> Result<(), Error> {
-        self.t.commit().await.map_err(Error::from)
+        // the outcome is unknown if this is dropped before completion
+        let in_flight = self.health.begin();
+        let res = self.t.commit().await.map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn rollback(self) -> Result<(), Error> {
-        self.t.rollback().await.map_err(Error::from)
+        // the outcome is unknown if this is dropped before completion
+        let in_flight = self.health.begin();
+        let res = self.t.rollback().await.map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn transaction(&mut self) -> Result<Transaction<'_>, Error> {
@@ -687,6 +1931,12 @@ impl Transaction<'_> {
             readonly: self.readonly,
             id: self.id,
             stmt_cache: self.stmt_cache.clone(),
+            canceller: self.canceller.clone(),
+            query_timeout: self.query_timeout,
+            unnamed: self.unnamed,
+            stmt_stats: self.stmt_stats.clone(),
+            checkout_wait: self.checkout_wait,
+            health: self.health.clone(),
             t: self.t.transaction().await?,
         })
     }
@@ -699,6 +1949,12 @@ impl Transaction<'_> {
             readonly: self.readonly,
             id: self.id,
             stmt_cache: self.stmt_cache.clone(),
+            canceller: self.canceller.clone(),
+            query_timeout: self.query_timeout,
+            unnamed: self.unnamed,
+            stmt_stats: self.stmt_stats.clone(),
+            checkout_wait: self.checkout_wait,
+            health: self.health.clone(),
             t: self.t.savepoint(name).await?,
         })
     }
@@ -706,6 +1962,18 @@ impl Transaction<'_> {
     pub fn cancel_token(&self) -> pg::CancelToken {
         self.t.cancel_token()
     }
+
+    /// Run `future` with the given timeout, cancelling the running query on the server
+    /// if the timeout elapses or the returned future is dropped before completion.
+    pub async fn cancellable<O>(
+        &self,
+        timeout: Option<Duration>,
+        future: impl Future<Output = Result<O, Error>>,
+    ) -> Result<O, Error> {
+        let canceller = self.canceller.clone();
+
+        cancel::cancellable(self.cancel_token(), canceller, &self.health, timeout, future).await
+    }
 }
 
 impl Transaction<'_> {
@@ -716,7 +1984,19 @@ impl Transaction<'_> {
         I: IntoIterator<Item = P>,
         I::IntoIter: ExactSizeIterator,
     {
-        self.t.query_raw(statement, params).await.map_err(Error::from)
+        if self.unnamed {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        // the outcome is unknown if this is dropped before completion
+        let in_flight = self.health.begin();
+        let res = self
+            .t
+            .query_raw(statement, params)
+            .await
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query_stream<T>(
@@ -743,21 +2023,54 @@ impl Transaction<'_> {
     where
         T: ?Sized + ToStatement,
     {
-        self.t.execute(statement, params).await.map_err(Error::from)
+        if self.unnamed {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        let in_flight = self.health.begin();
+        let res = self
+            .t
+            .execute(statement, params)
+            .await
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>, Error>
     where
         T: ?Sized + ToStatement,
     {
-        self.t.query(statement, params).await.map_err(Error::from)
+        if self.unnamed {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        let in_flight = self.health.begin();
+        let res = self
+            .t
+            .query(statement, params)
+            .await
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query_one<T>(&self, statement: &T, params: &[&(dyn ToSql + Sync)]) -> Result<Row, Error>
     where
         T: ?Sized + ToStatement,
     {
-        self.t.query_one(statement, params).await.map_err(Error::from)
+        if self.unnamed {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
+
+        let in_flight = self.health.begin();
+        let res = self
+            .t
+            .query_one(statement, params)
+            .await
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
     }
 
     pub async fn query_opt<T>(
@@ -768,23 +2081,442 @@ impl Transaction<'_> {
     where
         T: ?Sized + ToStatement,
     {
-        self.t.query_opt(statement, params).await.map_err(Error::from)
-    }
-}
+        if self.unnamed {
+            return Err(Error::Unsupported(UNTYPED_UNSUPPORTED));
+        }
 
-use thorn::macros::{Query, RowColumns, SqlFormatError};
+        let in_flight = self.health.begin();
+        let res = self
+            .t
+            .query_opt(statement, params)
+            .await
+            .map_err(|e| self.health.fail(e));
+        in_flight.finish();
+        res
+    }
 
-impl Client {
-    pub async fn prepare_cached2<'a, E: RowColumns>(
+    pub async fn execute_with_timeout<T>(
         &self,
-        query: &mut Query<'a, E>,
-    ) -> Result<Statement, Error> {
-        let key = match query.cached {
-            Some(_) => StatementCacheKey::typed::<E>(),
-            None => StatementCacheKey::borrowed(&query.q, &query.param_tys),
-        };
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.query_timeout, self.execute(statement, params))
+            .await
+    }
+
+    pub async fn query_with_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.query_timeout, self.query(statement, params))
+            .await
+    }
+
+    pub async fn query_one_with_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Row, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.query_timeout, self.query_one(statement, params))
+            .await
+    }
+
+    pub async fn query_opt_with_timeout<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Option<Row>, Error>
+    where
+        T: ?Sized + ToStatement,
+    {
+        self.cancellable(self.query_timeout, self.query_opt(statement, params))
+            .await
+    }
+}
+
+impl Client {
+    fn start_statement(&self) -> Started {
+        Started {
+            at: Instant::now(),
+            wait: Duration::from_nanos(self.checkout_wait.swap(0, Ordering::Relaxed)),
+        }
+    }
+
+    fn record_statement(
+        &self,
+        key: &StatementCacheKey,
+        query: &str,
+        started: Started,
+        outcome: Result<u64, &Error>,
+    ) {
+        if let Err(e) = outcome {
+            self.health.observe(e);
+        }
+
+        self.stmt_stats
+            .record(key, query, started, outcome.map_err(stats::sqlstate));
+    }
+}
+
+impl Transaction<'_> {
+    fn start_statement(&self) -> Started {
+        Started {
+            at: Instant::now(),
+            wait: Duration::from_nanos(self.checkout_wait.swap(0, Ordering::Relaxed)),
+        }
+    }
+
+    fn record_statement(
+        &self,
+        key: &StatementCacheKey,
+        query: &str,
+        started: Started,
+        outcome: Result<u64, &Error>,
+    ) {
+        if let Err(e) = outcome {
+            self.health.observe(e);
+        }
+
+        self.stmt_stats
+            .record(key, query, started, outcome.map_err(stats::sqlstate));
+    }
+}
+
+const NAMED_UNSUPPORTED: &str = "named prepared statements are not available with transaction pooling";
+
+/// Plain `query`/`execute` methods prepare named statements, which a transaction pooler may not route back
+const UNTYPED_UNSUPPORTED: &str =
+    "statements without parameter types are not available with transaction pooling, use `query_cached` instead";
+
+type TypedParams<'b> = Vec<(&'b (dyn ToSql + Sync), Type)>;
+
+/// Pairs each parameter with its type, for executing a query as an unnamed statement
+fn zip_typed<'b>(params: &[&'b (dyn ToSql + Sync)], types: &[Type]) -> Result<TypedParams<'b>, Error> {
+    if types.len() != params.len() {
+        return Err(Error::Unsupported(
+            "unnamed statements require the types of all parameters",
+        ));
+    }
+
+    Ok(params.iter().copied().zip(types.iter().cloned()).collect())
+}
+
+impl Client {
+    /// Prepare `query` with the given parameter types, reusing the statement if already cached on this connection.
+    ///
+    /// Parameters without a given type are inferred by the server.
+    pub async fn prepare_cached(&self, query: &str, types: &[Type]) -> Result<Statement, Error> {
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(NAMED_UNSUPPORTED));
+        }
+
+        if let Some(stmt) = self.stmt_cache.get(&StatementCacheKey::borrowed(query, types)) {
+            return Ok(stmt);
+        }
+
+        log::debug!("Preparing query: \"{query}\"");
+
+        let start = Instant::now();
+
+        let stmt = self
+            .client
+            .prepare_typed(check_readonly(query, self.readonly), types)
+            .await?;
+
+        self.stmt_cache.metrics().record_prepare(start.elapsed());
+
+        self.stmt_cache.set(
+            StaticStatementCacheKey::owned(query.to_owned(), types.to_vec()),
+            stmt.clone(),
+        );
+
+        Ok(stmt)
+    }
+
+    /// Evict a stale statement from the cache and prepare it again
+    async fn reprepare_cached(&self, query: &str, types: &[Type], err: Error) -> Result<Statement, Error> {
+        log::debug!("Re-preparing stale statement after error: {err}");
+
+        self.stmt_cache.remove(&StatementCacheKey::borrowed(query, types));
+        self.prepare_cached(query, types).await
+    }
+
+    /// Run `query` through the statement cache, see [`Client::prepare_cached`]
+    pub async fn query_cached(
+        &self,
+        query: &str,
+        types: &[Type],
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, Error> {
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<Vec<Row>, Error> = async {
+            if self.config.transaction_pooling {
+                let params = zip_typed(params, types)?;
+
+                return Ok(self
+                    .client
+                    .query_typed(check_readonly(query, self.readonly), &params)
+                    .await?);
+            }
+
+            let stmt = self.prepare_cached(query, types).await?;
+
+            match self.query(&stmt, params).await {
+                Err(e) if e.is_stale_statement() => {
+                    let stmt = self.reprepare_cached(query, types, e).await?;
+                    self.query(&stmt, params).await
+                }
+                res => res,
+            }
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &StatementCacheKey::borrowed(query, types),
+            query,
+            started,
+            res.as_ref().map(|rows| rows.len() as u64),
+        );
+
+        res
+    }
+
+    /// Execute `query` through the statement cache, see [`Client::prepare_cached`]
+    pub async fn execute_cached(
+        &self,
+        query: &str,
+        types: &[Type],
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, Error> {
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<u64, Error> = async {
+            if self.config.transaction_pooling {
+                let params = zip_typed(params, types)?;
+
+                return Ok(self
+                    .client
+                    .execute_typed(check_readonly(query, self.readonly), &params)
+                    .await?);
+            }
+
+            let stmt = self.prepare_cached(query, types).await?;
+
+            match self.execute(&stmt, params).await {
+                Err(e) if e.is_stale_statement() => {
+                    let stmt = self.reprepare_cached(query, types, e).await?;
+                    self.execute(&stmt, params).await
+                }
+                res => res,
+            }
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &StatementCacheKey::borrowed(query, types),
+            query,
+            started,
+            res.as_ref().copied(),
+        );
+
+        res
+    }
+}
+
+impl Transaction<'_> {
+    /// Prepare `query` with the given parameter types, reusing the statement if already cached on this connection.
+    ///
+    /// Parameters without a given type are inferred by the server.
+    pub async fn prepare_cached(&self, query: &str, types: &[Type]) -> Result<Statement, Error> {
+        if self.unnamed {
+            return Err(Error::Unsupported(NAMED_UNSUPPORTED));
+        }
+
+        if let Some(stmt) = self.stmt_cache.get(&StatementCacheKey::borrowed(query, types)) {
+            return Ok(stmt);
+        }
+
+        log::debug!("Preparing transaction query: \"{query}\"");
+
+        let start = Instant::now();
+
+        let stmt = self
+            .t
+            .prepare_typed(check_readonly(query, self.readonly), types)
+            .await?;
+
+        self.stmt_cache.metrics().record_prepare(start.elapsed());
+
+        self.stmt_cache.set(
+            StaticStatementCacheKey::owned(query.to_owned(), types.to_vec()),
+            stmt.clone(),
+        );
+
+        Ok(stmt)
+    }
+
+    /// Evict a stale statement from the cache so the next use re-prepares it, see `Transaction::evict_stale`
+    fn evict_stale_cached(&self, query: &str, types: &[Type], err: Error) -> Error {
+        if err.is_stale_statement() {
+            log::debug!("Evicting stale statement after error: {err}");
+            self.stmt_cache.remove(&StatementCacheKey::borrowed(query, types));
+        }
+
+        err
+    }
+
+    /// Run `query` through the statement cache, see [`Transaction::prepare_cached`]
+    pub async fn query_cached(
+        &self,
+        query: &str,
+        types: &[Type],
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, Error> {
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<Vec<Row>, Error> = async {
+            if self.unnamed {
+                let params = zip_typed(params, types)?;
+
+                return Ok(self
+                    .t
+                    .query_typed(check_readonly(query, self.readonly), &params)
+                    .await?);
+            }
+
+            self.query(&self.prepare_cached(query, types).await?, params)
+                .await
+                .map_err(|e| self.evict_stale_cached(query, types, e))
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &StatementCacheKey::borrowed(query, types),
+            query,
+            started,
+            res.as_ref().map(|rows| rows.len() as u64),
+        );
+
+        res
+    }
+
+    /// Execute `query` through the statement cache, see [`Transaction::prepare_cached`]
+    pub async fn execute_cached(
+        &self,
+        query: &str,
+        types: &[Type],
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, Error> {
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<u64, Error> = async {
+            if self.unnamed {
+                let params = zip_typed(params, types)?;
+
+                return Ok(self
+                    .t
+                    .execute_typed(check_readonly(query, self.readonly), &params)
+                    .await?);
+            }
+
+            self.execute(&self.prepare_cached(query, types).await?, params)
+                .await
+                .map_err(|e| self.evict_stale_cached(query, types, e))
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &StatementCacheKey::borrowed(query, types),
+            query,
+            started,
+            res.as_ref().copied(),
+        );
+
+        res
+    }
+}
+
+use thorn::macros::{Query, RowColumns, SqlFormatError};
+
+/// Pairs each parameter of a thorn query with its type, for executing it as an unnamed statement
+fn typed_params<'b, E: RowColumns>(query: &'b Query<'_, E>) -> Result<(&'b str, TypedParams<'b>), Error> {
+    let (q, tys) = match query.cached {
+        Some(cached) => (&cached.q, &cached.params),
+        None => (&query.q, &query.param_tys),
+    };
+
+    Ok((q, zip_typed(&query.params, tys)?))
+}
+
+/// SQL text of a thorn query, as recorded in [`StatementStats`]
+fn query_sql<'b, E: RowColumns>(query: &'b Query<'_, E>) -> &'b str {
+    match query.cached {
+        Some(cached) => &cached.q,
+        None => &query.q,
+    }
+}
+
+/// Verify that a statement cached under a typed key was prepared from the same SQL,
+/// as typed keys only identify the row type and so two queries sharing one would collide.
+#[cfg(debug_assertions)]
+fn check_collision<E: RowColumns>(cache: &StatementCache, query: &Query<'_, E>) -> Result<(), Error> {
+    if query.cached.is_none() {
+        return Ok(());
+    }
+
+    match cache.cached_sql(&cache_key(query)) {
+        Some(cached) if cached != query_sql(query) => Err(Error::StatementCacheCollision {
+            row_type: std::any::type_name::<E>(),
+            cached,
+            query: query_sql(query).to_owned(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn cache_key<'b, E: RowColumns>(query: &'b Query<'_, E>) -> StatementCacheKey<'b> {
+    match query.cached {
+        Some(_) => StatementCacheKey::typed::<E>(),
+        None => StatementCacheKey::borrowed(&query.q, &query.param_tys),
+    }
+}
+
+impl Client {
+    pub async fn prepare_cached2<'a, E: RowColumns>(
+        &self,
+        query: &mut Query<'a, E>,
+    ) -> Result<Statement, Error> {
+        if self.config.transaction_pooling {
+            return Err(Error::Unsupported(NAMED_UNSUPPORTED));
+        }
+
+        if let Some(stmt) = self.stmt_cache.get(&cache_key(query)) {
+            #[cfg(debug_assertions)]
+            check_collision(&self.stmt_cache, query)?;
 
-        if let Some(stmt) = self.stmt_cache.get(&key) {
             return Ok(stmt);
         }
 
@@ -795,24 +2527,38 @@ impl Client {
             None => (&query.q, &query.param_tys),
         };
 
+        let start = Instant::now();
+
         let stmt = self
             .client
             .prepare_typed(check_readonly(q, self.readonly), tys)
             .await?;
 
+        self.stmt_cache.metrics().record_prepare(start.elapsed());
+
         let key = match query.cached {
             Some(_) => StaticStatementCacheKey::typed::<E>(),
-            None => StaticStatementCacheKey::owned(
-                std::mem::take(&mut query.q),
-                std::mem::take(&mut query.param_tys),
-            ),
+            // NOTE: Cloned rather than taken so the query can be re-prepared if the statement goes stale
+            None => StaticStatementCacheKey::owned(query.q.clone(), query.param_tys.clone()),
         };
 
-        self.stmt_cache.set(key, stmt.clone());
+        self.stmt_cache.set_with_sql(key, stmt.clone(), q);
 
         Ok(stmt)
     }
 
+    /// Evict a stale statement from the cache and prepare it again
+    async fn reprepare_cached2<'a, E: RowColumns>(
+        &self,
+        query: &mut Query<'a, E>,
+        err: Error,
+    ) -> Result<Statement, Error> {
+        log::debug!("Re-preparing stale statement after error: {err}");
+
+        self.stmt_cache.remove(&cache_key(query));
+        self.prepare_cached2(query).await
+    }
+
     pub async fn query_stream2<'a, E: RowColumns>(
         &self,
         query: Result<Query<'a, E>, SqlFormatError>,
@@ -824,17 +2570,55 @@ impl Client {
         }
 
         let mut query = query?;
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
 
-        let stream = self
-            .query_raw(
-                &self.prepare_cached2(&mut query).await?,
-                slice_iter(&query.params),
-            )
-            .await?;
+        let res: Result<RowStream, Error> = async {
+            Ok(if self.config.transaction_pooling {
+                let (q, params) = typed_params(&query)?;
 
-        Ok(stream.map(|r| match r {
-            Ok(row) => Ok(E::from(row)),
-            Err(e) => Err(e.into()),
+                self.client
+                    .query_typed_raw(check_readonly(q, self.readonly), params)
+                    .await?
+            } else {
+                let stmt = self.prepare_cached2(&mut query).await?;
+
+                match self.query_raw(&stmt, slice_iter(&query.params)).await {
+                    Err(e) if e.is_stale_statement() => {
+                        let stmt = self.reprepare_cached2(&mut query, e).await?;
+                        self.query_raw(&stmt, slice_iter(&query.params)).await?
+                    }
+                    res => res?,
+                }
+            })
+        }
+        .await;
+
+        in_flight.finish();
+
+        let key = cache_key(&query);
+
+        let stream = match res {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.record_statement(&key, query_sql(&query), started, Err(&e));
+                return Err(e);
+            }
+        };
+
+        let mut recorder = StreamRecorder {
+            registry: self.stmt_stats.clone(),
+            key: key.to_static(),
+            query: query_sql(&query).to_owned(),
+            started,
+            rows: 0,
+            error: None,
+        };
+
+        Ok(stream.map(move |r| {
+            let r = r.map(E::from).map_err(Error::from);
+            recorder.observe(&r);
+            r
         }))
     }
 
@@ -856,11 +2640,46 @@ impl Client {
         query: Result<Query<'a, E>, SqlFormatError>,
     ) -> Result<E, Error> {
         let mut query = query?;
-        let row = self
-            .query_one(&self.prepare_cached2(&mut query).await?, &query.params)
-            .await?;
 
-        Ok(E::from(row))
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<E, Error> = async {
+            if self.config.transaction_pooling {
+                let (q, params) = typed_params(&query)?;
+
+                let row = self
+                    .client
+                    .query_typed_one(check_readonly(q, self.readonly), &params)
+                    .await?;
+
+                return Ok(E::from(row));
+            }
+
+            let stmt = self.prepare_cached2(&mut query).await?;
+
+            let row = match self.query_one(&stmt, &query.params).await {
+                Err(e) if e.is_stale_statement() => {
+                    let stmt = self.reprepare_cached2(&mut query, e).await?;
+                    self.query_one(&stmt, &query.params).await?
+                }
+                res => res?,
+            };
+
+            Ok(E::from(row))
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &cache_key(&query),
+            query_sql(&query),
+            started,
+            res.as_ref().map(|_| 1),
+        );
+
+        res
     }
 
     pub async fn query_opt2<'a, E: RowColumns>(
@@ -868,11 +2687,46 @@ impl Client {
         query: Result<Query<'a, E>, SqlFormatError>,
     ) -> Result<Option<E>, Error> {
         let mut query = query?;
-        let row = self
-            .query_opt(&self.prepare_cached2(&mut query).await?, &query.params)
-            .await?;
 
-        Ok(row.map(E::from))
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<Option<E>, Error> = async {
+            if self.config.transaction_pooling {
+                let (q, params) = typed_params(&query)?;
+
+                let row = self
+                    .client
+                    .query_typed_opt(check_readonly(q, self.readonly), &params)
+                    .await?;
+
+                return Ok(row.map(E::from));
+            }
+
+            let stmt = self.prepare_cached2(&mut query).await?;
+
+            let row = match self.query_opt(&stmt, &query.params).await {
+                Err(e) if e.is_stale_statement() => {
+                    let stmt = self.reprepare_cached2(&mut query, e).await?;
+                    self.query_opt(&stmt, &query.params).await?
+                }
+                res => res?,
+            };
+
+            Ok(row.map(E::from))
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &cache_key(&query),
+            query_sql(&query),
+            started,
+            res.as_ref().map(|row| row.is_some() as u64),
+        );
+
+        res
     }
 
     pub async fn execute2<'a, E: RowColumns>(
@@ -880,7 +2734,73 @@ impl Client {
         query: Result<Query<'a, E>, SqlFormatError>,
     ) -> Result<u64, Error> {
         let mut query = query?;
-        self.execute(&self.prepare_cached2(&mut query).await?, &query.params)
+
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<u64, Error> = async {
+            if self.config.transaction_pooling {
+                let (q, params) = typed_params(&query)?;
+
+                return Ok(self
+                    .client
+                    .execute_typed(check_readonly(q, self.readonly), &params)
+                    .await?);
+            }
+
+            let stmt = self.prepare_cached2(&mut query).await?;
+
+            match self.execute(&stmt, &query.params).await {
+                Err(e) if e.is_stale_statement() => {
+                    let stmt = self.reprepare_cached2(&mut query, e).await?;
+                    self.execute(&stmt, &query.params).await
+                }
+                res => res,
+            }
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &cache_key(&query),
+            query_sql(&query),
+            started,
+            res.as_ref().copied(),
+        );
+
+        res
+    }
+
+    pub async fn query2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<Vec<E>, Error> {
+        self.cancellable(self.config.timeouts.query, self.query2(query))
+            .await
+    }
+
+    pub async fn query_one2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<E, Error> {
+        self.cancellable(self.config.timeouts.query, self.query_one2(query))
+            .await
+    }
+
+    pub async fn query_opt2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<Option<E>, Error> {
+        self.cancellable(self.config.timeouts.query, self.query_opt2(query))
+            .await
+    }
+
+    pub async fn execute2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<u64, Error> {
+        self.cancellable(self.config.timeouts.query, self.execute2(query))
             .await
     }
 }
@@ -890,12 +2810,14 @@ impl Transaction<'_> {
         &self,
         query: &mut Query<'a, E>,
     ) -> Result<Statement, Error> {
-        let key = match query.cached {
-            Some(_) => StatementCacheKey::typed::<E>(),
-            None => StatementCacheKey::borrowed(&query.q, &query.param_tys),
-        };
+        if self.unnamed {
+            return Err(Error::Unsupported(NAMED_UNSUPPORTED));
+        }
+
+        if let Some(stmt) = self.stmt_cache.get(&cache_key(query)) {
+            #[cfg(debug_assertions)]
+            check_collision(&self.stmt_cache, query)?;
 
-        if let Some(stmt) = self.stmt_cache.get(&key) {
             return Ok(stmt);
         }
 
@@ -906,24 +2828,38 @@ impl Transaction<'_> {
             None => (&query.q, &query.param_tys),
         };
 
+        let start = Instant::now();
+
         let stmt = self
             .t
             .prepare_typed(check_readonly(q, self.readonly), tys)
             .await?;
 
+        self.stmt_cache.metrics().record_prepare(start.elapsed());
+
         let key = match query.cached {
             Some(_) => StaticStatementCacheKey::typed::<E>(),
-            None => StaticStatementCacheKey::owned(
-                std::mem::take(&mut query.q),
-                std::mem::take(&mut query.param_tys),
-            ),
+            // NOTE: Cloned rather than taken so the query can be re-prepared if the statement goes stale
+            None => StaticStatementCacheKey::owned(query.q.clone(), query.param_tys.clone()),
         };
 
-        self.stmt_cache.set(key, stmt.clone());
+        self.stmt_cache.set_with_sql(key, stmt.clone(), q);
 
         Ok(stmt)
     }
 
+    /// Evict a stale statement from the cache so the next use re-prepares it.
+    ///
+    /// Unlike with [`Client`], the query cannot be retried, as the error has aborted the transaction.
+    fn evict_stale<E: RowColumns>(&self, query: &Query<'_, E>, err: Error) -> Error {
+        if err.is_stale_statement() {
+            log::debug!("Evicting stale statement after error: {err}");
+            self.stmt_cache.remove(&cache_key(query));
+        }
+
+        err
+    }
+
     pub async fn query_stream2<'a, E: RowColumns>(
         &self,
         query: Result<Query<'a, E>, SqlFormatError>,
@@ -935,16 +2871,52 @@ impl Transaction<'_> {
         }
 
         let mut query = query?;
-        let stream = self
-            .query_raw(
-                &self.prepare_cached2(&mut query).await?,
-                slice_iter(&query.params),
-            )
-            .await?;
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<RowStream, Error> = async {
+            Ok(if self.unnamed {
+                let (q, params) = typed_params(&query)?;
+
+                self.t
+                    .query_typed_raw(check_readonly(q, self.readonly), params)
+                    .await?
+            } else {
+                self.query_raw(
+                    &self.prepare_cached2(&mut query).await?,
+                    slice_iter(&query.params),
+                )
+                .await
+                .map_err(|e| self.evict_stale(&query, e))?
+            })
+        }
+        .await;
+
+        in_flight.finish();
 
-        Ok(stream.map(|r| match r {
-            Ok(row) => Ok(E::from(row)),
-            Err(e) => Err(e.into()),
+        let key = cache_key(&query);
+
+        let stream = match res {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.record_statement(&key, query_sql(&query), started, Err(&e));
+                return Err(e);
+            }
+        };
+
+        let mut recorder = StreamRecorder {
+            registry: self.stmt_stats.clone(),
+            key: key.to_static(),
+            query: query_sql(&query).to_owned(),
+            started,
+            rows: 0,
+            error: None,
+        };
+
+        Ok(stream.map(move |r| {
+            let r = r.map(E::from).map_err(Error::from);
+            recorder.observe(&r);
+            r
         }))
     }
 
@@ -966,11 +2938,41 @@ impl Transaction<'_> {
         query: Result<Query<'a, E>, SqlFormatError>,
     ) -> Result<E, Error> {
         let mut query = query?;
-        let row = self
-            .query_one(&self.prepare_cached2(&mut query).await?, &query.params)
-            .await?;
 
-        Ok(E::from(row))
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<E, Error> = async {
+            if self.unnamed {
+                let (q, params) = typed_params(&query)?;
+
+                let row = self
+                    .t
+                    .query_typed_one(check_readonly(q, self.readonly), &params)
+                    .await?;
+
+                return Ok(E::from(row));
+            }
+
+            let row = self
+                .query_one(&self.prepare_cached2(&mut query).await?, &query.params)
+                .await
+                .map_err(|e| self.evict_stale(&query, e))?;
+
+            Ok(E::from(row))
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &cache_key(&query),
+            query_sql(&query),
+            started,
+            res.as_ref().map(|_| 1),
+        );
+
+        res
     }
 
     pub async fn query_opt2<'a, E: RowColumns>(
@@ -978,11 +2980,41 @@ impl Transaction<'_> {
         query: Result<Query<'a, E>, SqlFormatError>,
     ) -> Result<Option<E>, Error> {
         let mut query = query?;
-        let row = self
-            .query_opt(&self.prepare_cached2(&mut query).await?, &query.params)
-            .await?;
 
-        Ok(row.map(E::from))
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<Option<E>, Error> = async {
+            if self.unnamed {
+                let (q, params) = typed_params(&query)?;
+
+                let row = self
+                    .t
+                    .query_typed_opt(check_readonly(q, self.readonly), &params)
+                    .await?;
+
+                return Ok(row.map(E::from));
+            }
+
+            let row = self
+                .query_opt(&self.prepare_cached2(&mut query).await?, &query.params)
+                .await
+                .map_err(|e| self.evict_stale(&query, e))?;
+
+            Ok(row.map(E::from))
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &cache_key(&query),
+            query_sql(&query),
+            started,
+            res.as_ref().map(|row| row.is_some() as u64),
+        );
+
+        res
     }
 
     pub async fn execute2<'a, E: RowColumns>(
@@ -990,8 +3022,64 @@ impl Transaction<'_> {
         query: Result<Query<'a, E>, SqlFormatError>,
     ) -> Result<u64, Error> {
         let mut query = query?;
-        self.execute(&self.prepare_cached2(&mut query).await?, &query.params)
-            .await
+
+        let started = self.start_statement();
+        let in_flight = self.health.begin();
+
+        let res: Result<u64, Error> = async {
+            if self.unnamed {
+                let (q, params) = typed_params(&query)?;
+
+                return Ok(self
+                    .t
+                    .execute_typed(check_readonly(q, self.readonly), &params)
+                    .await?);
+            }
+
+            self.execute(&self.prepare_cached2(&mut query).await?, &query.params)
+                .await
+                .map_err(|e| self.evict_stale(&query, e))
+        }
+        .await;
+
+        in_flight.finish();
+
+        self.record_statement(
+            &cache_key(&query),
+            query_sql(&query),
+            started,
+            res.as_ref().copied(),
+        );
+
+        res
+    }
+
+    pub async fn query2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<Vec<E>, Error> {
+        self.cancellable(self.query_timeout, self.query2(query)).await
+    }
+
+    pub async fn query_one2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<E, Error> {
+        self.cancellable(self.query_timeout, self.query_one2(query)).await
+    }
+
+    pub async fn query_opt2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<Option<E>, Error> {
+        self.cancellable(self.query_timeout, self.query_opt2(query)).await
+    }
+
+    pub async fn execute2_with_timeout<'a, E: RowColumns>(
+        &self,
+        query: Result<Query<'a, E>, SqlFormatError>,
+    ) -> Result<u64, Error> {
+        self.cancellable(self.query_timeout, self.execute2(query)).await
     }
 }
 
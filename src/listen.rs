@@ -0,0 +1,153 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Weak},
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use pg::Notification;
+use tokio::sync::mpsc;
+
+use crate::{util::quote_ident, Error, Pool, PoolInner};
+
+pub(crate) const LISTEN_UNSUPPORTED: &str = "LISTEN is not available with transaction pooling";
+
+/// Item yielded by a [`Listener`]
+#[derive(Debug)]
+pub enum ListenEvent {
+    Notification(Notification),
+    /// The listener lost its connection and has since reconnected and re-subscribed,
+    /// so any notifications sent in between may have been missed.
+    Gap,
+}
+
+/// Stream of notifications from a dedicated connection created by [`Pool::listen`]
+///
+/// The connection is closed once this is dropped.
+pub struct Listener {
+    rx: mpsc::Receiver<ListenEvent>,
+}
+
+impl Listener {
+    pub async fn recv(&mut self) -> Option<ListenEvent> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for Listener {
+    type Item = ListenEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Pool {
+    /// Subscribe to the given channels on a dedicated connection, which is re-established
+    /// and re-subscribed through the [`Connector`](crate::Connector) if it is lost.
+    ///
+    /// This connection does not count towards `max_connections`, and is not closed along with the pool.
+    /// If the pool has been dropped, the stream ends the next time the connection is lost instead of reconnecting.
+    pub fn listen<I>(&self, channels: I) -> Result<Listener, Error>
+    where
+        I: IntoIterator<Item: Into<String>>,
+    {
+        if self.config.load().transaction_pooling {
+            return Err(Error::Unsupported(LISTEN_UNSUPPORTED));
+        }
+
+        let sql: String = channels
+            .into_iter()
+            .map(|channel| format!("LISTEN {};", quote_ident(&channel.into())))
+            .collect();
+
+        let (tx, rx) = mpsc::channel(self.config.load().channel_size.max(1));
+
+        tokio::spawn(listen_loop(Arc::downgrade(&self.0), sql, tx));
+
+        Ok(Listener { rx })
+    }
+}
+
+async fn listen_loop(pool: Weak<PoolInner>, sql: String, tx: mpsc::Sender<ListenEvent>) {
+    let mut failures = 0;
+    let mut connected_before = false;
+
+    loop {
+        let Some(pool) = pool.upgrade() else { break };
+
+        let config = pool.config.load_full();
+
+        let connecting = tokio::select! {
+            biased;
+            _ = tx.closed() => break,
+            res = pool.connector.connect(&config) => res,
+        };
+
+        drop(pool);
+
+        let res = match connecting {
+            Ok((client, conn, rx)) => {
+                // start forwarding before subscribing so nothing is missed
+                let rx = match conn.is_direct() {
+                    true => {
+                        conn.spawn_notifications_with(config.channel_size, config.notification_overflow, None)
+                    }
+                    false => rx,
+                };
+
+                match client.simple_query(&sql).await {
+                    Ok(_) => Ok((client, conn, rx)),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        // NOTE: The client must be kept alive, or else the connection is closed
+        let (_client, conn, mut notifications) = match res {
+            Ok(res) => res,
+            Err(e) => {
+                failures += 1;
+
+                log::error!("Error establishing listener connection: {e}");
+
+                tokio::select! {
+                    biased;
+                    _ = tx.closed() => break,
+                    _ = tokio::time::sleep(config.retry_policy.delay(failures)) => continue,
+                }
+            }
+        };
+
+        failures = 0;
+
+        log::debug!("Listening on connection {}", conn.id);
+
+        if connected_before && tx.send(ListenEvent::Gap).await.is_err() {
+            break;
+        }
+
+        connected_before = true;
+
+        loop {
+            let notif = tokio::select! {
+                biased;
+                _ = tx.closed() => return,
+                notif = notifications.recv() => notif,
+            };
+
+            match notif {
+                Some(notif) => {
+                    if tx.send(ListenEvent::Notification(notif)).await.is_err() {
+                        return;
+                    }
+                }
+                None => {
+                    log::warn!("Listener connection {} lost, reconnecting", conn.id);
+                    break;
+                }
+            }
+        }
+    }
+}
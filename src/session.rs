@@ -0,0 +1,47 @@
+use pg::{Client as PgClient, SimpleQueryMessage};
+
+use crate::Error;
+
+/// Information about the server session, captured when the connection is established
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Process ID of the backend serving this connection, as seen in `pg_stat_activity`
+    pub backend_pid: i32,
+    pub server_version: String,
+    pub server_encoding: String,
+    pub timezone: String,
+    pub is_superuser: bool,
+    pub in_hot_standby: bool,
+}
+
+impl SessionInfo {
+    // `in_hot_standby` only exists on PostgreSQL 14+, so fall back to `pg_is_in_recovery()`
+    const QUERY: &'static str = "SELECT pg_backend_pid(),
+        current_setting('server_version'),
+        current_setting('server_encoding'),
+        current_setting('TimeZone'),
+        current_setting('is_superuser') = 'on',
+        COALESCE(current_setting('in_hot_standby', true) = 'on', pg_is_in_recovery())";
+
+    pub async fn fetch(client: &PgClient) -> Result<SessionInfo, Error> {
+        for msg in client.simple_query(Self::QUERY).await? {
+            let SimpleQueryMessage::Row(row) = msg else {
+                continue;
+            };
+
+            let text = |idx| row.get(idx).unwrap_or_default().to_owned();
+            let flag = |idx| row.get(idx) == Some("t");
+
+            return Ok(SessionInfo {
+                backend_pid: row.get(0).and_then(|pid| pid.parse().ok()).unwrap_or_default(),
+                server_version: text(1),
+                server_encoding: text(2),
+                timezone: text(3),
+                is_superuser: flag(4),
+                in_hot_standby: flag(5),
+            });
+        }
+
+        Err(Error::ConnectionFailure)
+    }
+}
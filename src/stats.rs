@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+use pg::error::SqlState;
+
+use crate::{
+    key::{StatementCacheKey, StaticStatementCacheKey},
+    Error,
+};
+
+/// Client-side execution statistics for a single statement, aggregated across every connection in a pool
+///
+/// Similar to `pg_stat_statements`, but timed from the client's view, so latency includes
+/// network round-trips and statement preparation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatementStats {
+    pub query: String,
+    pub calls: u64,
+    /// Rows returned by queries, or affected by other statements
+    pub rows: u64,
+    pub total_time: Duration,
+    pub max_time: Duration,
+    /// Time spent waiting on the pool for a connection, attributed to the first statement run after each checkout
+    pub wait_time: Duration,
+    /// Failed executions by SQLSTATE, with `None` for errors not reported by the server, such as timeouts
+    pub errors: Vec<(Option<SqlState>, u64)>,
+}
+
+impl StatementStats {
+    pub fn mean_time(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            n => self.total_time / n.min(u32::MAX as u64) as u32,
+        }
+    }
+
+    pub fn error_count(&self) -> u64 {
+        self.errors.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// Start of a statement execution, see [`StatementStatsRegistry::record`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Started {
+    pub at: Instant,
+    pub wait: Duration,
+}
+
+/// Pool-wide [`StatementStats`], keyed by statement cache key
+#[derive(Default)]
+pub(crate) struct StatementStatsRegistry {
+    stats: scc::HashMap<StaticStatementCacheKey, StatementStats, foldhash::fast::RandomState>,
+}
+
+impl StatementStatsRegistry {
+    /// Record a completed execution, with `outcome` being the number of rows or the error
+    pub fn record(
+        &self,
+        key: &StatementCacheKey<'_>,
+        query: &str,
+        started: Started,
+        outcome: Result<u64, Option<SqlState>>,
+    ) {
+        let elapsed = started.at.elapsed();
+
+        let update = |stats: &mut StatementStats| {
+            stats.calls += 1;
+            stats.total_time += elapsed;
+            stats.max_time = stats.max_time.max(elapsed);
+            stats.wait_time += started.wait;
+
+            match outcome {
+                Ok(rows) => stats.rows += rows,
+                Err(ref code) => match stats.errors.iter_mut().find(|(c, _)| c == code) {
+                    Some((_, count)) => *count += 1,
+                    None => stats.errors.push((code.clone(), 1)),
+                },
+            }
+        };
+
+        // avoid allocating an owned key for statements that have been seen before
+        if self.stats.update(key, |_, stats| update(stats)).is_none() {
+            update(
+                self.stats
+                    .entry(key.to_static())
+                    .or_insert_with(|| StatementStats {
+                        query: query.to_owned(),
+                        ..StatementStats::default()
+                    })
+                    .get_mut(),
+            );
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<StatementStats> {
+        let mut snapshot = Vec::with_capacity(self.stats.len());
+        self.stats.scan(|_, stats| snapshot.push(stats.clone()));
+        snapshot
+    }
+
+    pub fn clear(&self) {
+        self.stats.clear();
+    }
+}
+
+/// Extract the SQLSTATE of an error for [`StatementStats::errors`]
+pub(crate) fn sqlstate(err: &Error) -> Option<SqlState> {
+    err.as_db_error().map(|e| e.code().clone())
+}
+
+/// Records a streamed execution once the stream is dropped, counting rows as they're received
+pub(crate) struct StreamRecorder {
+    pub registry: std::sync::Arc<StatementStatsRegistry>,
+    pub key: StaticStatementCacheKey,
+    pub query: String,
+    pub started: Started,
+    pub rows: u64,
+    pub error: Option<Option<SqlState>>,
+}
+
+impl StreamRecorder {
+    pub fn observe<T>(&mut self, res: &Result<T, Error>) {
+        match res {
+            Ok(_) => self.rows += 1,
+            Err(e) => self.error = Some(sqlstate(e)),
+        }
+    }
+}
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        let outcome = match self.error.take() {
+            Some(code) => Err(code),
+            None => Ok(self.rows),
+        };
+
+        self.registry
+            .record(&self.key.0, &self.query, self.started, outcome);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    convert::Infallible,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use pg::Notification;
+use tokio::sync::mpsc::{self, Receiver};
+
+use crate::{Client, Connection, Error};
+
+/// Decodes a value from a notification payload
+pub trait FromPayload: Sized {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    fn from_payload(payload: &str) -> Result<Self, Self::Error>;
+}
+
+impl FromPayload for String {
+    type Error = Infallible;
+
+    fn from_payload(payload: &str) -> Result<Self, Self::Error> {
+        Ok(payload.to_owned())
+    }
+}
+
+/// Decodes the payload as JSON
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromPayload for Json<T> {
+    type Error = serde_json::Error;
+
+    fn from_payload(payload: &str) -> Result<Self, Self::Error> {
+        serde_json::from_str(payload).map(Json)
+    }
+}
+
+/// Stream of decoded notifications on a single channel
+///
+/// Notifications on other channels are skipped, and decode errors are yielded per-message.
+pub struct Subscription<T> {
+    rx: Receiver<Notification>,
+    channel: String,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<T: FromPayload> Subscription<T> {
+    pub fn new(rx: Receiver<Notification>, channel: impl Into<String>) -> Self {
+        Subscription {
+            rx,
+            channel: channel.into(),
+            _ty: PhantomData,
+        }
+    }
+
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    pub async fn recv(&mut self) -> Option<Result<T, Error>> {
+        std::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, Error>>> {
+        loop {
+            let Some(notif) = std::task::ready!(self.rx.poll_recv(cx)) else {
+                return Poll::Ready(None);
+            };
+
+            if notif.channel() != self.channel {
+                continue;
+            }
+
+            return Poll::Ready(Some(T::from_payload(notif.payload()).map_err(|e| {
+                Error::PayloadError {
+                    channel: self.channel.clone(),
+                    error: Box::new(e),
+                }
+            })));
+        }
+    }
+}
+
+impl<T: FromPayload> Stream for Subscription<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl Connection {
+    /// Spawn the notification loop, yielding decoded notifications for only the given channel
+    ///
+    /// Note that this does not issue `LISTEN` for the channel, and that a connection checked out of
+    /// the pool must first be taken with [`Client::take_connection`], otherwise this waits for it to be released.
+    /// Prefer [`Client::subscribe`] in that case.
+    pub fn subscribe<T: FromPayload>(&self, channel: impl Into<String>, size: usize) -> Subscription<T> {
+        Subscription::new(self.spawn_notifications(size, None), channel)
+    }
+}
+
+impl Client {
+    /// Issue `LISTEN` for `channel` and take over this client's notifications, yielding decoded notifications
+    /// for only that channel. Afterwards, [`Client::recv_notif`] will return `None`.
+    ///
+    /// As notifications can no longer be delivered to this client, its connection is closed
+    /// rather than returned to the pool once dropped, which also ends the subscription.
+    pub async fn subscribe<T: FromPayload>(&mut self, channel: &str) -> Result<Subscription<T>, Error> {
+        if self.hub.is_some() {
+            return Err(Error::Unsupported(
+                "notifications have already been handed over to a hub",
+            ));
+        }
+
+        self.listen(channel).await?;
+
+        // replace with a receiver that's already closed
+        let rx = std::mem::replace(&mut self.rx, mpsc::channel(1).1);
+
+        self.poison();
+
+        Ok(Subscription::new(rx, channel))
+    }
+}
@@ -1,3 +1,28 @@
+use std::{
+    hash::BuildHasher,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Cheap random number in `[0, 1)`, good enough for jittering retry delays.
+pub(crate) fn random_unit() -> f64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let bits = foldhash::fast::RandomState::default().hash_one(COUNTER.fetch_add(1, Ordering::Relaxed));
+
+    // use the upper 53 bits as the mantissa
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Quote an SQL identifier, such as a channel name
+pub(crate) fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Quote an SQL string literal
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
 pub struct SqlIterator<'a> {
     sql: &'a str,
 }